@@ -0,0 +1,103 @@
+use cgmath::{ InnerSpace, Point3, Vector2, Vector3 };
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::terrain::chunk::CHUNK_SIZE;
+use crate::terrain::position::ChunkPos;
+
+// How much chunk loading work is allowed each frame, so crossing a chunk border doesn't
+// spend a whole frame on it
+#[derive(Copy, Clone, Debug)]
+pub struct LoadBudget {
+    // New chunks handed to the generation workers
+    pub generations_per_frame: usize,
+    // Finished chunk meshes uploaded to the GPU
+    pub uploads_per_frame: usize,
+}
+
+impl Default for LoadBudget {
+    fn default() -> Self {
+        Self {
+            generations_per_frame: 4,
+            uploads_per_frame: 8,
+        }
+    }
+}
+
+// Orders chunks by how soon the player is going to see them. Chunks get a lower value
+// the closer they are to the camera, and chunks behind the camera count as up to twice
+// as far away as the ones straight ahead.
+pub fn chunk_priority(
+    chunk_pos: ChunkPos,
+    camera_position: Point3<f32>,
+    camera_direction: Vector3<f32>
+) -> f32 {
+    let origin = chunk_pos.origin();
+    let center = Vector2::new(
+        (origin.x as f32) + (CHUNK_SIZE as f32) * 0.5,
+        (origin.z as f32) + (CHUNK_SIZE as f32) * 0.5
+    );
+    let to_chunk = center - Vector2::new(camera_position.x, camera_position.z);
+    let distance = to_chunk.magnitude();
+
+    let view = Vector2::new(camera_direction.x, camera_direction.z);
+    if distance < 1.0e-3 || view.magnitude2() < 1.0e-6 {
+        return distance;
+    }
+
+    let facing = view.normalize().dot(to_chunk / distance);
+    distance * (1.5 - 0.5 * facing)
+}
+
+struct LoadRequest {
+    priority: f32,
+    chunk_pos: ChunkPos,
+}
+
+impl PartialEq for LoadRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for LoadRequest {}
+
+impl PartialOrd for LoadRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LoadRequest {
+    // Reversed so the BinaryHeap hands out the lowest priority value first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+// Chunks waiting to be generated, most important first
+pub struct ChunkLoadQueue {
+    heap: BinaryHeap<LoadRequest>,
+}
+
+impl ChunkLoadQueue {
+    pub fn new() -> Self {
+        Self { heap: BinaryHeap::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    pub fn push(&mut self, chunk_pos: ChunkPos, priority: f32) {
+        self.heap.push(LoadRequest { priority, chunk_pos });
+    }
+
+    pub fn pop(&mut self) -> Option<ChunkPos> {
+        self.heap.pop().map(|request| request.chunk_pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
@@ -1,71 +1,146 @@
 use cgmath::{ Point3, Vector3 };
 use winit::event::*;
 use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
 
-use crate::terrain::generator::TerrainGenerator;
+use crate::terrain::generator::WorldGenerator;
 use crate::terrain::chunk::Chunk;
+use crate::terrain::biome::Biome;
+use crate::terrain::block::{ BlockId, BlockRegistry };
+use crate::terrain::position::{ ChunkPos, WorldPos };
+use crate::terrain::raycast::{ self, RaycastHit };
+use crate::terrain::world::World;
+use crate::worker_pool::{ self, JobHandle, WorkerPool };
 use super::camera_controller::CameraController;
+use super::chunk_queue::{ self, ChunkLoadQueue, LoadBudget };
+
+// How far away from the camera blocks can be targeted
+const REACH: f32 = 8.0;
 
 pub struct GameState {
     camera_controller: CameraController,
     camera_position: Point3<f32>,
     camera_direction: Vector3<f32>,
     camera_up: Vector3<f32>,
-    terrain_generator: TerrainGenerator,
+    generation_pool: WorkerPool<ChunkPos, Chunk>,
+    block_registry: Arc<BlockRegistry>,
     render_distance: i32,
-    chunks: HashMap<(i32, i32), Chunk>,
-    loaded_chunks: HashSet<(i32, i32)>, // Track which chunks are currently loaded
+    world: World,
+    loaded_chunks: HashSet<ChunkPos>, // Track which chunks are currently loaded
+    pending_chunks: HashMap<ChunkPos, JobHandle>, // Chunks being generated in the background
+    load_queue: ChunkLoadQueue, // Chunks in range that haven't started generating yet
+    load_budget: LoadBudget,
     chunks_updated: bool,
+    hotbar: Vec<BlockId>, // Blocks that can be selected with the number keys
+    selected_block: BlockId,
 }
 
 impl GameState {
-    pub fn new() -> Self {
+    pub fn new(
+        block_registry: Arc<BlockRegistry>,
+        generator: Box<dyn WorldGenerator>,
+        load_budget: LoadBudget
+    ) -> Self {
+        let generation_pool = WorkerPool::new(
+            "chunk-generation",
+            worker_pool::generation_threads(),
+            move |pos| generator.generate_chunk(pos)
+        );
+
+        // The first nine solid blocks, in registry order
+        let hotbar: Vec<BlockId> = block_registry
+            .blocks()
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.solid)
+            .map(|(index, _)| BlockId(index as u16))
+            .take(9)
+            .collect();
+        let selected_block = hotbar.first().copied().unwrap_or(BlockId::STONE);
+
         let mut state = Self {
             camera_controller: CameraController::new(100.0, 0.1),
             camera_position: Point3::new(0.0, 70.0, 0.0),
             camera_direction: Vector3::new(0.0, -0.5, -1.0),
             camera_up: Vector3::new(0.0, 1.0, 0.0),
-            terrain_generator: TerrainGenerator::new(42),
-            chunks: HashMap::new(),
+            generation_pool,
+            block_registry,
+            world: World::new(),
             render_distance: 6, // Number of chunks to load in each direction
             loaded_chunks: HashSet::new(),
+            pending_chunks: HashMap::new(),
+            load_queue: ChunkLoadQueue::new(),
+            load_budget,
             chunks_updated: false,
+            hotbar,
+            selected_block,
         };
 
-        // Generate initial chunks
+        // Start generating the initial chunks
         state.update_chunks();
         state
     }
 
+    // Queues generation of chunks that came into range, unloads the ones that left it and
+    // picks up chunks that finished generating. Returns whether the loaded chunks changed.
     pub fn update_chunks(&mut self) -> bool {
         let chunks_in_range = self.get_chunks_in_range();
 
         // Find chunks to unload
-        let chunks_to_unload: Vec<(i32, i32)> = self.loaded_chunks
+        let chunks_to_unload: Vec<ChunkPos> = self.loaded_chunks
             .difference(&chunks_in_range)
             .cloned()
             .collect();
 
         // Find chunks to load
-        let chunks_to_load: Vec<(i32, i32)> = chunks_in_range
+        let chunks_to_load: Vec<ChunkPos> = chunks_in_range
             .difference(&self.loaded_chunks)
+            .filter(|pos| !self.pending_chunks.contains_key(pos))
             .cloned()
             .collect();
 
-        // Store the lengths before we move the vectors
-        let has_changes = !chunks_to_load.is_empty() || !chunks_to_unload.is_empty();
+        let mut has_changes = !chunks_to_unload.is_empty();
 
         // Unload chunks
         for chunk_pos in chunks_to_unload {
-            self.chunks.remove(&chunk_pos);
+            self.world.remove_chunk(chunk_pos);
             self.loaded_chunks.remove(&chunk_pos);
         }
 
-        // Load new chunks
+        // Cancel chunks that went out of range before they finished generating
+        self.pending_chunks.retain(|chunk_pos, job| {
+            let in_range = chunks_in_range.contains(chunk_pos);
+            if !in_range {
+                job.cancel();
+            }
+            in_range
+        });
+
+        // Queue new chunks. The queue is rebuilt every frame since the priorities change as
+        // the camera moves and turns.
+        self.load_queue.clear();
         for chunk_pos in chunks_to_load {
-            let voxels = self.terrain_generator.generate_chunk(chunk_pos.0, chunk_pos.1);
-            self.chunks.insert(chunk_pos, Chunk::new(voxels, chunk_pos.0, chunk_pos.1));
-            self.loaded_chunks.insert(chunk_pos);
+            self.load_queue.push(chunk_pos, self.chunk_priority(chunk_pos));
+        }
+
+        // Start generating the most important chunks, as many as the budget allows
+        for _ in 0..self.load_budget.generations_per_frame {
+            let Some(chunk_pos) = self.load_queue.pop() else {
+                break;
+            };
+            let job = self.generation_pool.submit(chunk_pos);
+            self.pending_chunks.insert(chunk_pos, job);
+        }
+
+        // Load chunks that are done generating
+        for (id, chunk) in self.generation_pool.results() {
+            let chunk_pos = chunk.pos;
+            if self.pending_chunks.get(&chunk_pos).is_some_and(|job| job.id == id) {
+                self.pending_chunks.remove(&chunk_pos);
+                self.world.insert_chunk(chunk);
+                self.loaded_chunks.insert(chunk_pos);
+                has_changes = true;
+            }
         }
 
         self.chunks_updated = has_changes;
@@ -73,35 +148,95 @@ impl GameState {
     }
 
     // Get the chunks that should be loaded based on render distance
-    fn get_chunks_in_range(&self) -> HashSet<(i32, i32)> {
-        let (center_x, center_z) = self.get_current_chunk();
+    fn get_chunks_in_range(&self) -> HashSet<ChunkPos> {
+        let center = self.get_current_chunk();
         let mut chunks = HashSet::new();
 
         for x in -self.render_distance..=self.render_distance {
             for z in -self.render_distance..=self.render_distance {
-                chunks.insert((center_x + x, center_z + z));
+                chunks.insert(center.offset(x, z));
             }
         }
         chunks
     }
 
+    // Lower values should be loaded and shown first, see chunk_queue::chunk_priority
+    pub fn chunk_priority(&self, chunk_pos: ChunkPos) -> f32 {
+        chunk_queue::chunk_priority(
+            chunk_pos,
+            self.camera_position,
+            self.camera_direction
+        )
+    }
+
+    // Number of chunks loaded in each direction around the camera
+    pub fn render_distance(&self) -> i32 {
+        self.render_distance
+    }
+
+    pub fn load_budget(&self) -> LoadBudget {
+        self.load_budget
+    }
+
+    // Number of chunks in range that are still waiting to be generated
+    pub fn queued_chunks(&self) -> usize {
+        self.load_queue.len() + self.pending_chunks.len()
+    }
+
     pub fn chunks_updated(&self) -> bool {
         self.chunks_updated
     }
 
     // Convert camera position to chunk coordinates
-    fn get_current_chunk(&self) -> (i32, i32) {
-        let chunk_size = 16; // Must match the value in TerrainGenerator
-        (
-            (self.camera_position.x / (chunk_size as f32)).floor() as i32,
-            (self.camera_position.z / (chunk_size as f32)).floor() as i32,
-        )
+    fn get_current_chunk(&self) -> ChunkPos {
+        WorldPos::containing(self.camera_position).chunk()
     }
 
     pub fn handle_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        let slot = match key {
+            VirtualKeyCode::Key1 => Some(0),
+            VirtualKeyCode::Key2 => Some(1),
+            VirtualKeyCode::Key3 => Some(2),
+            VirtualKeyCode::Key4 => Some(3),
+            VirtualKeyCode::Key5 => Some(4),
+            VirtualKeyCode::Key6 => Some(5),
+            VirtualKeyCode::Key7 => Some(6),
+            VirtualKeyCode::Key8 => Some(7),
+            VirtualKeyCode::Key9 => Some(8),
+            _ => None,
+        };
+        if let Some(slot) = slot {
+            if let (ElementState::Pressed, Some(block)) = (state, self.hotbar.get(slot)) {
+                self.selected_block = *block;
+            }
+            return true;
+        }
         self.camera_controller.process_keyboard(key, state)
     }
 
+    // Removes the targeted block. The renderer picks up the change and remeshes the
+    // chunk, along with the neighbors sharing a border with the block.
+    pub fn break_block(&mut self) {
+        if let Some(hit) = self.target() {
+            self.world.set_block(hit.pos, BlockId::AIR);
+        }
+    }
+
+    // Puts the selected block against the targeted face, unless the camera is in the way
+    pub fn place_block(&mut self) {
+        let Some(hit) = self.target() else {
+            return;
+        };
+        if hit.adjacent == WorldPos::containing(self.camera_position) {
+            return;
+        }
+        self.world.set_block(hit.adjacent, self.selected_block);
+    }
+
+    pub fn selected_block(&self) -> BlockId {
+        self.selected_block
+    }
+
     pub fn handle_mouse_motion(&mut self, dx: f64, dy: f64) {
         self.camera_controller.process_mouse(dx, dy);
     }
@@ -120,8 +255,48 @@ impl GameState {
         self.update_chunks();
     }
 
-    pub fn chunks(&self) -> &HashMap<(i32, i32), Chunk> {
-        &self.chunks
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    // Chunks changed by breaking or placing blocks since the last call
+    pub fn take_changed_chunks(&mut self) -> Vec<ChunkPos> {
+        self.world.take_changes()
+    }
+
+    // Average number of bytes used by each loaded chunk
+    pub fn average_chunk_memory(&self) -> usize {
+        if self.world.chunk_count() == 0 {
+            return 0;
+        }
+        let total: usize = self.world
+            .chunks()
+            .map(|chunk| chunk.memory_usage())
+            .sum();
+        total / self.world.chunk_count()
+    }
+
+    // Biome of the column the camera is in, once its chunk has loaded
+    pub fn current_biome(&self) -> Option<Biome> {
+        let pos = WorldPos::containing(self.camera_position);
+        let chunk = self.world.chunk(pos.chunk())?;
+        let local = pos.local();
+        Some(chunk.biome(local.x, local.z))
+    }
+
+    // The solid block the camera is looking at, if it's within reach
+    pub fn target(&self) -> Option<RaycastHit> {
+        raycast::raycast(
+            &self.world,
+            &self.block_registry,
+            self.camera_position,
+            self.camera_direction,
+            REACH
+        )
+    }
+
+    pub fn block_registry(&self) -> &Arc<BlockRegistry> {
+        &self.block_registry
     }
 
     pub fn camera_position(&self) -> Point3<f32> {
@@ -1,4 +1,6 @@
 mod game_state;
 mod camera_controller;
+mod chunk_queue;
 
+pub use chunk_queue::LoadBudget;
 pub use game_state::GameState;
@@ -3,14 +3,23 @@ use winit::{
     event_loop::{ ControlFlow, EventLoop },
     window::{ WindowBuilder, CursorGrabMode },
 };
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Instant;
 
 mod game;
 mod renderer;
 mod terrain;
+mod worker_pool;
 
-use game::GameState;
-use renderer::RenderState;
+use game::{ GameState, LoadBudget };
+use renderer::{ CameraOptions, Projection, RenderState };
+use terrain::block::BlockRegistry;
+use terrain::config::TerrainConfig;
+use terrain::flat::{ CheckerboardGenerator, SuperflatGenerator, VoidGenerator };
+use terrain::generator::{ TerrainGenerator, WorldGenerator };
+use terrain::heightmap::{ EdgeMode, HeightmapGenerator, HeightmapOptions };
 
 struct InputState {
     // Used to track whether the game is active or not. When the game is active, the cursor is
@@ -19,6 +28,134 @@ struct InputState {
     game_active: bool,
 }
 
+// Returns the value following a command line flag, e.g. `--terrain terrain.toml`
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next()
+}
+
+// Returns the number following a command line flag, or the default if the flag is missing
+// or isn't followed by a number
+fn number_arg<T: FromStr>(flag: &str, default: T) -> T {
+    match arg_value(flag) {
+        Some(value) =>
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("{} needs a number, got {}, using the default", flag, value);
+                default
+            }),
+        None => default,
+    }
+}
+
+fn camera_options() -> CameraOptions {
+    let defaults = CameraOptions::default();
+
+    // Reverse-Z gives the best depth precision, --no-reverse-z switches to a regular
+    // projection with a far plane for comparison
+    let projection = if std::env::args().any(|arg| arg == "--no-reverse-z") {
+        Projection::Standard
+    } else {
+        Projection::ReverseZInfinite
+    };
+
+    let mut fov = number_arg("--fov", defaults.fov);
+    if !(10.0..=170.0).contains(&fov) {
+        eprintln!("--fov must be between 10 and 170 degrees, using the default");
+        fov = defaults.fov;
+    }
+    let mut near = number_arg("--near", defaults.near);
+    if near <= 0.0 {
+        eprintln!("--near must be above 0, using the default");
+        near = defaults.near;
+    }
+
+    CameraOptions { projection, fov, near }
+}
+
+// At least one chunk has to be generated and uploaded per frame or loading never finishes
+fn load_budget() -> LoadBudget {
+    let defaults = LoadBudget::default();
+    LoadBudget {
+        generations_per_frame: number_arg("--generations-per-frame", defaults.generations_per_frame).max(1),
+        uploads_per_frame: number_arg("--uploads-per-frame", defaults.uploads_per_frame).max(1),
+    }
+}
+
+// Picks the world generator from the command line, see the options in README.md
+fn create_generator(registry: &BlockRegistry) -> Box<dyn WorldGenerator> {
+    match arg_value("--generator").as_deref() {
+        None | Some("terrain") => Box::new(create_terrain_generator(registry)),
+        Some("superflat") => {
+            let preset = arg_value("--preset").unwrap_or(SuperflatGenerator::DEFAULT_PRESET.to_string());
+            let generator = SuperflatGenerator::from_preset(&preset, registry).unwrap_or_else(|error| {
+                eprintln!("{}: {}, using the default preset", preset, error);
+                SuperflatGenerator::from_preset(SuperflatGenerator::DEFAULT_PRESET, registry).unwrap()
+            });
+            Box::new(generator)
+        }
+        Some("heightmap") => {
+            match create_heightmap_generator(registry) {
+                Ok(generator) => Box::new(generator),
+                Err(error) => {
+                    eprintln!("{}, using the default terrain", error);
+                    Box::new(create_terrain_generator(registry))
+                }
+            }
+        }
+        Some("void") => Box::new(VoidGenerator),
+        Some("checkerboard") => Box::new(CheckerboardGenerator),
+        Some(name) => {
+            eprintln!("Unknown generator {}, using the default terrain", name);
+            Box::new(create_terrain_generator(registry))
+        }
+    }
+}
+
+fn create_heightmap_generator(registry: &BlockRegistry) -> Result<HeightmapGenerator, String> {
+    let heightmap = arg_value("--heightmap").ok_or("--heightmap <image> is missing")?;
+    let color_map = arg_value("--colormap");
+
+    let defaults = HeightmapOptions::default();
+    let number = |flag: &str, default: f64| -> Result<f64, String> {
+        match arg_value(flag) {
+            Some(value) => value.parse().map_err(|_| format!("{} needs a number, got {}", flag, value)),
+            None => Ok(default),
+        }
+    };
+    let edges = match arg_value("--edges").as_deref() {
+        None | Some("clamp") => EdgeMode::Clamp,
+        Some("tile") => EdgeMode::Tile,
+        Some(other) => {
+            return Err(format!("--edges must be tile or clamp, got {}", other));
+        }
+    };
+    let options = HeightmapOptions {
+        scale: number("--height-scale", defaults.scale)?,
+        offset: number("--height-offset", defaults.offset)?,
+        edges,
+    };
+
+    HeightmapGenerator::load(
+        Path::new(&heightmap),
+        color_map.as_deref().map(Path::new),
+        options,
+        registry
+    ).map_err(|error| format!("{}: {}", heightmap, error))
+}
+
+fn create_terrain_generator(registry: &BlockRegistry) -> TerrainGenerator {
+    let terrain_config = match arg_value("--terrain") {
+        Some(path) =>
+            TerrainConfig::load(Path::new(&path)).unwrap_or_else(|error| {
+                eprintln!("{}: {}, using the default terrain", path, error);
+                TerrainConfig::default()
+            }),
+        None => TerrainConfig::default(),
+    };
+    TerrainGenerator::new(42, terrain_config, registry)
+}
+
 fn main() {
     pollster::block_on(run());
 }
@@ -27,8 +164,14 @@ async fn run() {
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().with_title("Vox3D").build(&event_loop).unwrap();
 
-    let mut game_state = GameState::new();
-    let mut render_state = RenderState::new(&window, &game_state).await;
+    let block_registry = Arc::new(BlockRegistry::default());
+    let generator = create_generator(&block_registry);
+    let mut game_state = GameState::new(block_registry, generator, load_budget());
+    let mut render_state = RenderState::new(
+        &window,
+        game_state.block_registry(),
+        camera_options()
+    ).await;
 
     let mut input_state = InputState {
         game_active: false,
@@ -38,10 +181,10 @@ async fn run() {
 
     event_loop.run(move |event, _, control_flow| {
         match event {
-            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
-                if input_state.game_active {
-                    game_state.handle_mouse_motion(delta.0, delta.1);
-                }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if
+                input_state.game_active
+            => {
+                game_state.handle_mouse_motion(delta.0, delta.1);
             }
             Event::WindowEvent { ref event, window_id } if window_id == window.id() => {
                 match event {
@@ -49,12 +192,21 @@ async fn run() {
                         state: ElementState::Pressed,
                         button: MouseButton::Left,
                         ..
-                    } => {
-                        if !input_state.game_active {
-                            input_state.game_active = true;
-                            let _ = window
-                                .set_cursor_grab(CursorGrabMode::Locked)
-                                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked));
+                    } if !input_state.game_active => {
+                        input_state.game_active = true;
+                        let _ = window
+                            .set_cursor_grab(CursorGrabMode::Locked)
+                            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked));
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button,
+                        ..
+                    } if input_state.game_active => {
+                        match button {
+                            MouseButton::Left => game_state.break_block(),
+                            MouseButton::Right => game_state.place_block(),
+                            _ => {}
                         }
                     }
                     WindowEvent::KeyboardInput {
@@ -71,6 +223,9 @@ async fn run() {
                                     *control_flow = ControlFlow::Exit;
                                 }
                             }
+                            VirtualKeyCode::G if *state == ElementState::Pressed => {
+                                render_state.toggle_meshing_mode(&game_state);
+                            }
                             _ => {
                                 if input_state.game_active {
                                     game_state.handle_keyboard(*keycode, *state);
@@ -97,9 +252,12 @@ async fn run() {
 
                 if input_state.game_active {
                     game_state.update(dt);
+                } else {
+                    // Keep picking up generated chunks while the game is paused
+                    game_state.update_chunks();
                 }
 
-                render_state.update(&game_state);
+                render_state.update(&mut game_state);
 
                 match render_state.render() {
                     Ok(_) => {}
@@ -0,0 +1,27 @@
+// Draws the outline around the block the camera is looking at, see OutlineVertex in vertex.rs
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    camera_pos: vec3<f32>,
+    fog_end: f32,
+    depth_bias: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> @builtin(position) vec4<f32> {
+    let position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    // Move the line towards the camera so it isn't hidden by the face it lies on
+    return vec4<f32>(position.xy, position.z + camera.depth_bias * position.w, position.w);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 0.6);
+}
@@ -1,4 +1,34 @@
-use cgmath::{ perspective, Deg, Matrix4, Point3, Vector3 };
+use cgmath::{ perspective, Deg, Matrix4, Point3, Rad, Vector3 };
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Projection {
+    // Regular perspective with depth going from 0 at the near plane to 1 at the far plane
+    Standard,
+    // Depth goes from 1 at the near plane to 0 at infinity. Floats have the most precision
+    // near 0, which reversing spreads evenly over the distance, and nothing is ever clipped
+    // by a far plane.
+    ReverseZInfinite,
+}
+
+// Camera settings picked on the command line
+#[derive(Copy, Clone, Debug)]
+pub struct CameraOptions {
+    pub projection: Projection,
+    // Vertical field of view in degrees
+    pub fov: f32,
+    // Distance to the near plane, anything closer to the camera is clipped
+    pub near: f32,
+}
+
+impl Default for CameraOptions {
+    fn default() -> Self {
+        Self {
+            projection: Projection::ReverseZInfinite,
+            fov: 45.0,
+            near: 0.1,
+        }
+    }
+}
 
 pub struct Camera {
     pub position: Point3<f32>,
@@ -8,18 +38,20 @@ pub struct Camera {
     fovy: f32,
     znear: f32,
     zfar: f32,
+    projection: Projection,
 }
 
 impl Camera {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, options: CameraOptions) -> Self {
         Self {
             position: Point3::new(0.0, 0.0, 10.0), // Start 10 units back
             direction: Vector3::new(0.0, 0.0, -1.0),
             up: Vector3::new(0.0, 1.0, 0.0), // Y is up
             aspect: (width as f32) / (height as f32),
-            fovy: 45.0,
-            znear: 0.1,
+            fovy: options.fov,
+            znear: options.near,
             zfar: 100.0,
+            projection: options.projection,
         }
     }
 
@@ -28,15 +60,66 @@ impl Camera {
         let view = Matrix4::look_to_rh(self.position, self.direction, self.up);
 
         // Create projection matrix
-        let proj = perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar);
+        let proj = match self.projection {
+            Projection::Standard => {
+                OPENGL_TO_WGPU_MATRIX * perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar)
+            }
+            Projection::ReverseZInfinite => {
+                reverse_z_infinite_perspective(Deg(self.fovy), self.aspect, self.znear)
+            }
+        };
 
         // Combine them
-        OPENGL_TO_WGPU_MATRIX * proj * view
+        proj * view
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = (width as f32) / (height as f32);
     }
+
+    // The far plane sits at the edge of the loaded world so nothing that's loaded gets
+    // clipped. The reverse-Z projection has no far plane, but the fog still uses it.
+    pub fn set_far(&mut self, zfar: f32) {
+        self.zfar = zfar;
+    }
+
+    // Depth buffer value for "nothing drawn here yet"
+    pub fn depth_clear_value(&self) -> f32 {
+        match self.projection {
+            Projection::Standard => 1.0,
+            Projection::ReverseZInfinite => 0.0,
+        }
+    }
+
+    // How a fragment's depth has to compare to the stored depth for it to be in front
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        match self.projection {
+            Projection::Standard => wgpu::CompareFunction::Less,
+            Projection::ReverseZInfinite => wgpu::CompareFunction::Greater,
+        }
+    }
+
+    // A small clip space depth offset towards the camera, for drawing lines over faces. It's
+    // scaled by w in the shader, so it's a fixed offset of the depth buffer value.
+    pub fn depth_bias(&self) -> f32 {
+        const BIAS: f32 = 1.0e-5;
+        match self.projection {
+            Projection::Standard => -BIAS,
+            Projection::ReverseZInfinite => BIAS,
+        }
+    }
+}
+
+// Maps view space depth -znear to 1 and infinity to 0, already in wgpu's 0 to 1 depth range
+#[rustfmt::skip]
+fn reverse_z_infinite_perspective(fovy: Deg<f32>, aspect: f32, znear: f32) -> Matrix4<f32> {
+    let f = 1.0 / (Rad::from(fovy).0 * 0.5).tan();
+    Matrix4::new(
+        f / aspect, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, 0.0, -1.0,
+        0.0, 0.0, znear, 0.0,
+    )
 }
 
 // We need this because wgpu's coordinate system is based on DirectX/Metal
@@ -1,15 +1,26 @@
+use cgmath::Vector3;
 use wgpu::util::DeviceExt;
 
-use super::vertex::Vertex;
+use super::frustum::Aabb;
+use super::vertex::{ ChunkInstance, Vertex };
+use crate::terrain::position::ChunkPos;
 
 // Holds rendering data for a single chunk
 pub struct ChunkMesh {
     pub vertex_buffer: wgpu::Buffer,
+    pub instance_buffer: wgpu::Buffer,
     pub num_vertices: u32,
+    pub num_quads: u32,
+    // World space bounds of the mesh, used for frustum culling
+    pub bounds: Aabb,
 }
 
 impl ChunkMesh {
-    pub fn new(device: &wgpu::Device, vertices: &[Vertex]) -> Self {
+    pub fn new(
+        device: &wgpu::Device,
+        vertices: &[Vertex],
+        pos: ChunkPos
+    ) -> Self {
         let vertex_buffer = device.create_buffer_init(
             &(wgpu::util::BufferInitDescriptor {
                 label: Some("Chunk Vertex Buffer"),
@@ -18,9 +29,86 @@ impl ChunkMesh {
             })
         );
 
+        let origin = pos.origin();
+        let instance = ChunkInstance {
+            origin: [origin.x, origin.y, origin.z],
+        };
+        let instance_buffer = device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("Chunk Instance Buffer"),
+                contents: bytemuck::cast_slice(&[instance]),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        );
+
+        let mut min = [u32::MAX; 3];
+        let mut max = [0; 3];
+        for vertex in vertices {
+            let position = vertex.local_position();
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        let origin = Vector3::from(instance.origin).cast::<f32>().unwrap();
+        let bounds = if vertices.is_empty() {
+            Aabb { min: origin, max: origin }
+        } else {
+            Aabb {
+                min: origin + Vector3::from(min).cast::<f32>().unwrap(),
+                max: origin + Vector3::from(max).cast::<f32>().unwrap(),
+            }
+        };
+
         Self {
             vertex_buffer,
+            instance_buffer,
             num_vertices: vertices.len() as u32,
+            // Every quad is made of 4 vertices shared by its two triangles
+            num_quads: (vertices.len() / 4) as u32,
+            bounds,
         }
     }
 }
+
+// An index buffer shared by every chunk mesh. Since all chunk geometry is made of quads,
+// the indices are the same repeating pattern of two triangles per 4 vertices.
+pub struct QuadIndexBuffer {
+    pub buffer: wgpu::Buffer,
+    capacity: u32,
+}
+
+impl QuadIndexBuffer {
+    pub fn new(device: &wgpu::Device, capacity: u32) -> Self {
+        Self {
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+        }
+    }
+
+    // Makes sure there are enough indices to draw the given number of quads
+    pub fn reserve(&mut self, device: &wgpu::Device, quads: u32) {
+        if quads <= self.capacity {
+            return;
+        }
+        self.capacity = quads.next_power_of_two();
+        self.buffer = Self::create_buffer(device, self.capacity);
+    }
+
+    fn create_buffer(device: &wgpu::Device, quads: u32) -> wgpu::Buffer {
+        let indices: Vec<u32> = (0..quads)
+            .flat_map(|quad| {
+                let base = quad * 4;
+                [base, base + 1, base + 2, base + 2, base + 3, base]
+            })
+            .collect();
+
+        device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("Quad Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        )
+    }
+}
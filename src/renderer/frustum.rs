@@ -0,0 +1,53 @@
+use cgmath::{ InnerSpace, Matrix, Matrix4, Vector3, Vector4 };
+
+// Axis aligned bounding box in world space
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+// The six planes of a view-projection matrix, with normals pointing inwards
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // Extracts the planes straight from the matrix (Gribb/Hartmann). wgpu clip space depth
+    // goes from 0 to w, unlike OpenGL's -w to w. With a reverse-Z infinite projection the
+    // z >= 0 plane ends up at infinity and never culls anything.
+    pub fn from_view_projection(view_proj: &Matrix4<f32>) -> Self {
+        let row0 = view_proj.row(0);
+        let row1 = view_proj.row(1);
+        let row2 = view_proj.row(2);
+        let row3 = view_proj.row(3);
+
+        let planes = [
+            row3 + row0, // Left
+            row3 - row0, // Right
+            row3 + row1, // Bottom
+            row3 - row1, // Top
+            row2, // Near, or far with reverse-Z
+            row3 - row2, // Far, or near with reverse-Z
+        ].map(|plane| {
+            // Normalizing isn't needed for the inside test, but keeps it well behaved
+            let length = plane.truncate().magnitude();
+            if length > 0.0 { plane / length } else { plane }
+        });
+
+        Self { planes }
+    }
+
+    // Returns false only when the box is completely outside one of the planes
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner of the box furthest along the plane normal
+            let corner = Vector3::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z }
+            );
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
@@ -1,133 +1,385 @@
+use std::sync::Arc;
+
 use super::vertex::Vertex;
+use crate::terrain::biome::Biome;
+use crate::terrain::block::{ BlockId, BlockRegistry };
+use crate::terrain::chunk::{ Chunk, Section, CHUNK_SIZE, SECTION_HEIGHT };
+use crate::terrain::position::ChunkPos;
+use crate::terrain::world::World;
+
+// The chunks bordering the one being meshed. Missing neighbors haven't been loaded yet,
+// so faces towards them are kept until the neighbor shows up and the chunk gets remeshed.
+pub struct ChunkNeighbors {
+    pub pos_x: Option<Arc<Chunk>>,
+    pub neg_x: Option<Arc<Chunk>>,
+    pub pos_z: Option<Arc<Chunk>>,
+    pub neg_z: Option<Arc<Chunk>>,
+}
+
+impl ChunkNeighbors {
+    pub fn from_world(world: &World, pos: ChunkPos) -> Self {
+        Self {
+            pos_x: world.chunk(pos.offset(1, 0)).cloned(),
+            neg_x: world.chunk(pos.offset(-1, 0)).cloned(),
+            pos_z: world.chunk(pos.offset(0, 1)).cloned(),
+            neg_z: world.chunk(pos.offset(0, -1)).cloned(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshingMode {
+    // One quad for every visible block face
+    Naive,
+    // Neighboring coplanar faces of the same block are merged into larger quads
+    Greedy,
+}
+
+// The six sides of a block, in the order used to index per-side arrays. The same order
+// is used for the normal index packed into each vertex, see the normals table in shader.wgsl.
+#[derive(Copy, Clone)]
+enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Face {
+    const ALL: [Face; 6] = [Face::PosX, Face::NegX, Face::PosY, Face::NegY, Face::PosZ, Face::NegZ];
+
+    fn normal(self) -> [i32; 3] {
+        match self {
+            Face::PosX => [1, 0, 0],
+            Face::NegX => [-1, 0, 0],
+            Face::PosY => [0, 1, 0],
+            Face::NegY => [0, -1, 0],
+            Face::PosZ => [0, 0, 1],
+            Face::NegZ => [0, 0, -1],
+        }
+    }
+
+    // Index of the axis the face points along
+    fn axis(self) -> usize {
+        match self {
+            Face::PosX | Face::NegX => 0,
+            Face::PosY | Face::NegY => 1,
+            Face::PosZ | Face::NegZ => 2,
+        }
+    }
+
+    fn is_positive(self) -> bool {
+        matches!(self, Face::PosX | Face::PosY | Face::PosZ)
+    }
+}
 
 pub struct MeshGenerator;
 
 impl MeshGenerator {
-    // Create a single face of a cube so we can optimize for not drawing every face
-    fn create_face(pos: [f32; 3], normal: [f32; 3], color: [f32; 3]) -> Vec<Vertex> {
-        let (x, y, z) = (pos[0], pos[1], pos[2]);
-
-        match normal {
-            [0.0, 0.0, 1.0] =>
-                vec![
-                    // Front face
-                    Vertex { position: [x - 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y - 0.5, z + 0.5], color, normal }
-                ],
-            [0.0, 0.0, -1.0] =>
-                vec![
-                    // Back face
-                    Vertex { position: [x - 0.5, y - 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y - 0.5, z - 0.5], color, normal }
-                ],
-            [0.0, 1.0, 0.0] =>
-                vec![
-                    // Top face
-                    Vertex { position: [x - 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z - 0.5], color, normal }
-                ],
-            [0.0, -1.0, 0.0] =>
-                vec![
-                    // Bottom face
-                    Vertex { position: [x - 0.5, y - 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y - 0.5, z - 0.5], color, normal }
-                ],
-            [1.0, 0.0, 0.0] =>
-                vec![
-                    // Right face
-                    Vertex { position: [x + 0.5, y - 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x + 0.5, y - 0.5, z - 0.5], color, normal }
-                ],
-            [-1.0, 0.0, 0.0] =>
-                vec![
-                    // Left face
-                    Vertex { position: [x - 0.5, y - 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y - 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z + 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y + 0.5, z - 0.5], color, normal },
-                    Vertex { position: [x - 0.5, y - 0.5, z - 0.5], color, normal }
-                ],
-            _ => vec![], // Invalid normal
+    // Create one side of the box spanning from min to max, in block corner coordinates
+    // relative to the chunk. Single block faces are just a unit sized box, greedy meshing
+    // passes in larger boxes to cover many blocks at once. The corners are in counter
+    // clockwise order when looking at the face, ready for the shared quad index buffer.
+    fn create_quad(min: [u32; 3], max: [u32; 3], face: Face, block: BlockId, biome: Biome) -> [Vertex; 4] {
+        let (x0, y0, z0) = (min[0], min[1], min[2]);
+        let (x1, y1, z1) = (max[0], max[1], max[2]);
+
+        let corners = match face {
+            // Front face
+            Face::PosZ => [
+                [x0, y0, z1],
+                [x1, y0, z1],
+                [x1, y1, z1],
+                [x0, y1, z1],
+            ],
+            // Back face
+            Face::NegZ => [
+                [x0, y0, z0],
+                [x0, y1, z0],
+                [x1, y1, z0],
+                [x1, y0, z0],
+            ],
+            // Top face
+            Face::PosY => [
+                [x0, y1, z0],
+                [x0, y1, z1],
+                [x1, y1, z1],
+                [x1, y1, z0],
+            ],
+            // Bottom face
+            Face::NegY => [
+                [x0, y0, z0],
+                [x1, y0, z0],
+                [x1, y0, z1],
+                [x0, y0, z1],
+            ],
+            // Right face
+            Face::PosX => [
+                [x1, y0, z0],
+                [x1, y1, z0],
+                [x1, y1, z1],
+                [x1, y0, z1],
+            ],
+            // Left face
+            Face::NegX => [
+                [x0, y0, z0],
+                [x0, y0, z1],
+                [x0, y1, z1],
+                [x0, y1, z0],
+            ],
+        };
+
+        corners.map(|position| Vertex::new(position, face as u32, block, biome))
+    }
+
+    // Looks up a block relative to the chunk, reaching into the neighboring chunks for
+    // positions just past the horizontal borders
+    fn block_at(chunk: &Chunk, neighbors: &ChunkNeighbors, x: i32, y: i32, z: i32) -> BlockId {
+        let neighbor = if x >= CHUNK_SIZE {
+            &neighbors.pos_x
+        } else if x < 0 {
+            &neighbors.neg_x
+        } else if z >= CHUNK_SIZE {
+            &neighbors.pos_z
+        } else if z < 0 {
+            &neighbors.neg_z
+        } else {
+            return chunk.get(x, y, z);
+        };
+
+        match neighbor {
+            Some(neighbor) => neighbor.get(x.rem_euclid(CHUNK_SIZE), y, z.rem_euclid(CHUNK_SIZE)),
+            None => BlockId::AIR,
         }
     }
 
-    pub fn generate_chunk_mesh(voxels: &[bool], chunk_x: i32, chunk_z: i32) -> Vec<Vertex> {
-        let chunk_size = 16;
-        let max_height = 256;
+    // Sides of an opaque section that can have visible faces on them, indexed by Face.
+    // A side is covered when the section next to it is opaque too.
+    fn exposed_sides(
+        chunk: &Chunk,
+        neighbors: &ChunkNeighbors,
+        index: usize,
+        registry: &BlockRegistry
+    ) -> [bool; 6] {
+        let is_opaque = |chunk: Option<&Chunk>, index: Option<usize>| -> bool {
+            chunk
+                .zip(index)
+                .and_then(|(chunk, index)| chunk.section(index))
+                .is_some_and(|section| section.is_opaque(registry))
+        };
+
+        [
+            !is_opaque(neighbors.pos_x.as_deref(), Some(index)),
+            !is_opaque(neighbors.neg_x.as_deref(), Some(index)),
+            !is_opaque(Some(chunk), Some(index + 1)),
+            !is_opaque(Some(chunk), index.checked_sub(1)),
+            !is_opaque(neighbors.pos_z.as_deref(), Some(index)),
+            !is_opaque(neighbors.neg_z.as_deref(), Some(index)),
+        ]
+    }
+
+    pub fn generate_chunk_mesh(
+        chunk: &Chunk,
+        neighbors: &ChunkNeighbors,
+        registry: &BlockRegistry,
+        mode: MeshingMode
+    ) -> Vec<Vertex> {
         let mut vertices = Vec::new();
 
-        let get_voxel = |x: i32, y: i32, z: i32| -> bool {
-            if x < 0 || x >= chunk_size || y < 0 || y >= max_height || z < 0 || z >= chunk_size {
-                return false;
+        // Only sections with something in them need to be looked at
+        for (index, section) in chunk.sections() {
+            // Inside an opaque section only blocks on its exposed sides can have visible faces,
+            // and a section with no exposed sides is buried and can be skipped
+            let is_opaque = section.is_opaque(registry);
+            let exposed = if is_opaque {
+                Self::exposed_sides(chunk, neighbors, index, registry)
+            } else {
+                [true; 6]
+            };
+            if !exposed.contains(&true) {
+                continue;
             }
-            let index = (x + z * chunk_size + y * chunk_size * chunk_size) as usize;
-            voxels[index]
+
+            let section_mesher = SectionMesher {
+                chunk,
+                neighbors,
+                registry,
+                section,
+                base_y: (index as i32) * SECTION_HEIGHT,
+                is_opaque,
+                exposed,
+            };
+
+            match mode {
+                MeshingMode::Naive => section_mesher.mesh_naive(&mut vertices),
+                MeshingMode::Greedy => section_mesher.mesh_greedy(&mut vertices),
+            }
+        }
+
+        vertices
+    }
+}
+
+// Everything needed to mesh a single section of a chunk
+struct SectionMesher<'a> {
+    chunk: &'a Chunk,
+    neighbors: &'a ChunkNeighbors,
+    registry: &'a BlockRegistry,
+    section: &'a Section,
+    base_y: i32,
+    is_opaque: bool,
+    exposed: [bool; 6],
+}
+
+impl SectionMesher<'_> {
+    // Returns the block whose face is visible at the given section position, if any, along
+    // with the biome to tint it with. Untinted blocks all get the same biome so greedy
+    // meshing can merge them across biome borders.
+    fn visible_face(&self, pos: [i32; 3], face: Face) -> Option<(BlockId, Biome)> {
+        let block = self.section.get(pos[0], pos[1], pos[2]);
+        if block.is_air() {
+            return None;
+        }
+
+        // A face is hidden when the block next to it is opaque, or the same kind of block
+        // so there are no faces between neighboring water blocks
+        let [dx, dy, dz] = face.normal();
+        let neighbor = MeshGenerator::block_at(
+            self.chunk,
+            self.neighbors,
+            pos[0] + dx,
+            self.base_y + pos[1] + dy,
+            pos[2] + dz
+        );
+        if self.registry.is_opaque(neighbor) || neighbor == block {
+            return None;
+        }
+
+        let biome = if self.registry.get(block).tinted {
+            self.chunk.biome(pos[0], pos[2])
+        } else {
+            Biome::default()
         };
+        Some((block, biome))
+    }
+
+    // Whether a slice of the section along the face's axis can have any visible faces.
+    // In an opaque section only the outermost slice of an exposed side can.
+    fn slice_has_faces(&self, face: Face, slice: i32) -> bool {
+        if !self.exposed[face as usize] {
+            return false;
+        }
+        if !self.is_opaque {
+            return true;
+        }
+        let outermost = if face.is_positive() { SECTION_HEIGHT - 1 } else { 0 };
+        slice == outermost
+    }
+
+    // Emits a quad covering the blocks from pos with the given size, in section coordinates
+    fn emit(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        pos: [i32; 3],
+        size: [i32; 3],
+        face: Face,
+        (block, biome): (BlockId, Biome)
+    ) {
+        let min = [pos[0] as u32, (self.base_y + pos[1]) as u32, pos[2] as u32];
+        let max = [min[0] + (size[0] as u32), min[1] + (size[1] as u32), min[2] + (size[2] as u32)];
+        vertices.extend(MeshGenerator::create_quad(min, max, face, block, biome));
+    }
 
-        for x in 0..chunk_size {
-            for y in 0..max_height {
-                for z in 0..chunk_size {
-                    if !get_voxel(x, y, z) {
-                        continue;
+    fn mesh_naive(&self, vertices: &mut Vec<Vertex>) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..SECTION_HEIGHT {
+                for z in 0..CHUNK_SIZE {
+                    for face in Face::ALL {
+                        let pos = [x, y, z];
+                        if !self.slice_has_faces(face, pos[face.axis()]) {
+                            continue;
+                        }
+                        if let Some(visible) = self.visible_face(pos, face) {
+                            self.emit(vertices, pos, [1, 1, 1], face, visible);
+                        }
                     }
+                }
+            }
+        }
+    }
 
-                    let world_x = chunk_x * chunk_size + x;
-                    let world_z = chunk_z * chunk_size + z;
+    // Sweeps each slice of the section along every face direction, building a mask of the
+    // visible faces and merging runs of equal blocks with the same tint into the largest
+    // rectangles it can
+    fn mesh_greedy(&self, vertices: &mut Vec<Vertex>) {
+        // Sections are cubes, so every slice is SIZE x SIZE whatever the axis
+        const SIZE: i32 = SECTION_HEIGHT;
+        let mut mask = [None; (SIZE * SIZE) as usize];
 
-                    let color = if y < 5 {
-                        [0.7, 0.7, 0.3] // Sand
-                    } else if y < 12 {
-                        [0.3, 0.5, 0.2] // Grass
-                    } else {
-                        [0.5, 0.5, 0.5] // Stone
-                    };
+        for face in Face::ALL {
+            let d = face.axis();
+            let u = (d + 1) % 3;
+            let v = (d + 2) % 3;
 
-                    let pos = [world_x as f32, y as f32, world_z as f32];
+            for slice in 0..SIZE {
+                if !self.slice_has_faces(face, slice) {
+                    continue;
+                }
 
-                    // Add faces only if adjacent block is air
-                    if !get_voxel(x, y, z + 1) {
-                        vertices.extend(Self::create_face(pos, [0.0, 0.0, 1.0], color));
-                    }
-                    if !get_voxel(x, y, z - 1) {
-                        vertices.extend(Self::create_face(pos, [0.0, 0.0, -1.0], color));
-                    }
-                    if !get_voxel(x, y + 1, z) {
-                        vertices.extend(Self::create_face(pos, [0.0, 1.0, 0.0], color));
-                    }
-                    if !get_voxel(x, y - 1, z) {
-                        vertices.extend(Self::create_face(pos, [0.0, -1.0, 0.0], color));
-                    }
-                    if !get_voxel(x + 1, y, z) {
-                        vertices.extend(Self::create_face(pos, [1.0, 0.0, 0.0], color));
+                for j in 0..SIZE {
+                    for i in 0..SIZE {
+                        let mut pos = [0; 3];
+                        pos[d] = slice;
+                        pos[u] = i;
+                        pos[v] = j;
+                        mask[(i + j * SIZE) as usize] = self.visible_face(pos, face);
                     }
-                    if !get_voxel(x - 1, y, z) {
-                        vertices.extend(Self::create_face(pos, [-1.0, 0.0, 0.0], color));
+                }
+
+                for j in 0..SIZE {
+                    let mut i = 0;
+                    while i < SIZE {
+                        let Some(visible) = mask[(i + j * SIZE) as usize] else {
+                            i += 1;
+                            continue;
+                        };
+
+                        // Grow the quad along u as far as the same block continues
+                        let mut width = 1;
+                        while i + width < SIZE && mask[(i + width + j * SIZE) as usize] == Some(visible) {
+                            width += 1;
+                        }
+
+                        // Then along v for as long as whole rows of the same width match
+                        let mut height = 1;
+                        while
+                            j + height < SIZE &&
+                            (i..i + width).all(|k| mask[(k + (j + height) * SIZE) as usize] == Some(visible))
+                        {
+                            height += 1;
+                        }
+
+                        for row in j..j + height {
+                            for k in i..i + width {
+                                mask[(k + row * SIZE) as usize] = None;
+                            }
+                        }
+
+                        let mut pos = [0; 3];
+                        pos[d] = slice;
+                        pos[u] = i;
+                        pos[v] = j;
+                        let mut size = [1; 3];
+                        size[u] = width;
+                        size[v] = height;
+                        self.emit(vertices, pos, size, face, visible);
+
+                        i += width;
                     }
                 }
             }
         }
-
-        vertices
     }
 }
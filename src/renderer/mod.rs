@@ -4,5 +4,7 @@ mod vertex;
 mod mesh_generator;
 mod chunk_mesh;
 mod fps_display;
+mod frustum;
 
+pub use camera::{ CameraOptions, Projection };
 pub use render_state::RenderState;
@@ -1,16 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
 
 use wgpu::{ self, util::DeviceExt };
 use wgpu_glyph::{ ab_glyph, GlyphBrush, GlyphBrushBuilder, Section, Text };
 use winit::window::Window;
 use crate::game::GameState;
+use crate::terrain::biome::Biome;
+use crate::terrain::block::BlockRegistry;
+use crate::terrain::chunk::Chunk;
+use crate::terrain::position::{ ChunkPos, WorldPos };
+use crate::worker_pool::{ self, JobHandle, WorkerPool };
 
 use super::fps_display::FpsDisplay;
-use super::chunk_mesh::ChunkMesh;
-use super::mesh_generator::MeshGenerator;
-use super::{ camera::OPENGL_TO_WGPU_MATRIX, vertex::Vertex };
-use super::camera::Camera;
-use cgmath::{ perspective, Deg, Matrix4, Point3, SquareMatrix, Vector3 };
+use super::chunk_mesh::{ ChunkMesh, QuadIndexBuffer };
+use super::frustum::Frustum;
+use super::mesh_generator::{ ChunkNeighbors, MeshGenerator, MeshingMode };
+use super::vertex::{ ChunkInstance, OutlineVertex, Vertex };
+use super::camera::{ Camera, CameraOptions };
+use crate::terrain::chunk::CHUNK_SIZE;
+use cgmath::{ Matrix4, Point3, SquareMatrix, Vector3 };
 
 // This is the uniform buffer that will hold our camera matrix
 #[repr(C)]
@@ -18,7 +26,9 @@ use cgmath::{ perspective, Deg, Matrix4, Point3, SquareMatrix, Vector3 };
 struct CameraUniform {
     view_proj: [[f32; 4]; 4],
     camera_pos: [f32; 3],
-    _padding: u32, // Necessary for alignment
+    fog_end: f32, // Also pads camera_pos to 16 bytes
+    depth_bias: f32, // Added to the clip space depth of the block outline
+    _padding: [f32; 3],
 }
 
 impl CameraUniform {
@@ -26,16 +36,73 @@ impl CameraUniform {
         Self {
             view_proj: Matrix4::identity().into(),
             camera_pos: [0.0, 0.0, 0.0],
-            _padding: 0,
+            fog_end: 0.0,
+            depth_bias: 0.0,
+            _padding: [0.0; 3],
         }
     }
 
     fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().into();
         self.camera_pos = camera.position.into();
+        self.depth_bias = camera.depth_bias();
     }
 }
 
+// The shader looks up vertex colors by block id in this table. The alpha channel is 1 for
+// blocks that are tinted by their biome, using the biome_tints table.
+const MAX_BLOCKS: usize = 256;
+const MAX_BIOMES: usize = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlockColorsUniform {
+    colors: [[f32; 4]; MAX_BLOCKS],
+    biome_tints: [[f32; 4]; MAX_BIOMES],
+}
+
+impl BlockColorsUniform {
+    fn new(registry: &BlockRegistry) -> Self {
+        // Ids past the end of the tables would still be packed into vertices and read past
+        // the end in the shader, and a vertex only has 3 bits for the biome
+        assert!(registry.blocks().len() <= MAX_BLOCKS, "more than {} blocks registered", MAX_BLOCKS);
+        assert!(Biome::ALL.len() <= MAX_BIOMES, "more than {} biomes", MAX_BIOMES);
+
+        let mut colors = [[0.0; 4]; MAX_BLOCKS];
+        for (color, block) in colors.iter_mut().zip(registry.blocks()) {
+            let tinted = if block.tinted { 1.0 } else { 0.0 };
+            *color = [block.color[0], block.color[1], block.color[2], tinted];
+        }
+        let mut biome_tints = [[1.0; 4]; MAX_BIOMES];
+        for (tint, biome) in biome_tints.iter_mut().zip(Biome::ALL) {
+            let [r, g, b] = biome.tint();
+            *tint = [r, g, b, 1.0];
+        }
+        Self { colors, biome_tints }
+    }
+}
+
+// Everything a worker thread needs to build the mesh of one chunk
+struct MeshJob {
+    chunk: Arc<Chunk>,
+    neighbors: ChunkNeighbors,
+    registry: Arc<BlockRegistry>,
+    mode: MeshingMode,
+}
+
+struct MeshResult {
+    pos: ChunkPos,
+    vertices: Vec<Vertex>,
+}
+
+struct PendingMesh {
+    job: JobHandle,
+    // The number of the edit this mesh is rebuilt for. Meshes of the same edit are
+    // uploaded in the same frame, so a block moving across a chunk border never leaves
+    // a frame with the face missing on both sides.
+    edit: Option<u64>,
+}
+
 pub struct RenderState {
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -43,19 +110,39 @@ pub struct RenderState {
     config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
     render_pipeline: wgpu::RenderPipeline,
+    outline_pipeline: wgpu::RenderPipeline, // Draws the edges of the targeted block
+    outline_buffer: wgpu::Buffer,
     camera: Camera,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     depth_texture: wgpu::TextureView,
-    chunk_meshes: HashMap<(i32, i32), ChunkMesh>,
+    chunk_meshes: HashMap<ChunkPos, ChunkMesh>,
+    quad_index_buffer: QuadIndexBuffer,
+    mesh_pool: WorkerPool<MeshJob, MeshResult>,
+    pending_meshes: HashMap<ChunkPos, PendingMesh>, // Chunks being meshed in the background
+    ready_meshes: Vec<(u64, MeshResult)>, // Finished meshes waiting for their turn to upload
+    meshing_mode: MeshingMode,
+    edit_count: u64, // Numbers the edits queued for remeshing
+    drawn_chunks: usize,
+    culled_chunks: usize,
     fps_display: FpsDisplay,
+    loaded_chunks: usize,
+    queued_chunks: usize,
+    average_chunk_memory: usize,
+    current_biome: Option<Biome>,
+    target: Option<(String, WorldPos)>, // Name and position of the block the camera is looking at
+    selected_block: String,
     glyph_brush: GlyphBrush<()>,
     staging_belt: wgpu::util::StagingBelt,
 }
 
 impl RenderState {
-    pub async fn new(window: &Window, game_state: &GameState) -> Self {
+    pub async fn new(
+        window: &Window,
+        block_registry: &BlockRegistry,
+        camera_options: CameraOptions
+    ) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -102,7 +189,7 @@ impl RenderState {
         surface.configure(&device, &config);
 
         // New camera setup
-        let camera = Camera::new(size.width, size.height);
+        let camera = Camera::new(size.width, size.height, camera_options);
 
         // Create camera uniform and buffer
         let mut camera_uniform = CameraUniform::new();
@@ -116,6 +203,14 @@ impl RenderState {
             })
         );
 
+        let block_colors_buffer = device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("Block Colors Buffer"),
+                contents: bytemuck::cast_slice(&[BlockColorsUniform::new(block_registry)]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        );
+
         // Create bind group layout
         let camera_bind_group_layout = device.create_bind_group_layout(
             &(wgpu::BindGroupLayoutDescriptor {
@@ -130,6 +225,16 @@ impl RenderState {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("camera_bind_group_layout"),
             })
@@ -144,6 +249,10 @@ impl RenderState {
                         binding: 0,
                         resource: camera_buffer.as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: block_colors_buffer.as_entire_binding(),
+                    },
                 ],
                 label: Some("camera_bind_group"),
             })
@@ -172,7 +281,7 @@ impl RenderState {
                 vertex: wgpu::VertexState {
                     module: &shader,
                     entry_point: "vs_main",
-                    buffers: &[Vertex::desc()],
+                    buffers: &[Vertex::desc(), ChunkInstance::desc()],
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &shader,
@@ -197,7 +306,7 @@ impl RenderState {
                 depth_stencil: Some(wgpu::DepthStencilState {
                     format: wgpu::TextureFormat::Depth32Float,
                     depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
+                    depth_compare: camera.depth_compare(),
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
                 }),
@@ -210,18 +319,30 @@ impl RenderState {
             })
         );
 
+        let outline_pipeline = Self::create_outline_pipeline(
+            &device,
+            &render_pipeline_layout,
+            config.format,
+            &camera
+        );
+        let outline_buffer = device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("Outline Vertex Buffer"),
+                size: (std::mem::size_of::<OutlineVertex>() *
+                    OutlineVertex::COUNT) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        );
+
         // Create depth texture
         let depth_texture = Self::create_depth_texture(&device, &config);
 
-        let mut all_vertices = Vec::new();
-        for chunk in game_state.chunks().values() {
-            all_vertices.extend(
-                MeshGenerator::generate_chunk_mesh(&chunk.voxels, chunk.chunk_x, chunk.chunk_z)
-            );
-        }
         let font = ab_glyph::FontArc
             ::try_from_slice(include_bytes!("../../assets/FiraSans-Regular.ttf"))
             .unwrap();
+        let quad_index_buffer = QuadIndexBuffer::new(&device, 4096);
+
         let glyph_brush = GlyphBrushBuilder::using_font(font).build(&device, config.format);
 
         Self {
@@ -231,64 +352,251 @@ impl RenderState {
             config,
             size,
             render_pipeline,
+            outline_pipeline,
+            outline_buffer,
             camera,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
             depth_texture,
             chunk_meshes: HashMap::new(),
+            quad_index_buffer,
+            mesh_pool: WorkerPool::new("chunk-meshing", worker_pool::meshing_threads(), |job: MeshJob| {
+                MeshResult {
+                    pos: job.chunk.pos,
+                    vertices: MeshGenerator::generate_chunk_mesh(
+                        &job.chunk,
+                        &job.neighbors,
+                        &job.registry,
+                        job.mode
+                    ),
+                }
+            }),
+            pending_meshes: HashMap::new(),
+            ready_meshes: Vec::new(),
+            meshing_mode: MeshingMode::Greedy,
+            edit_count: 0,
+            drawn_chunks: 0,
+            culled_chunks: 0,
             fps_display: FpsDisplay::new(),
+            loaded_chunks: 0,
+            queued_chunks: 0,
+            average_chunk_memory: 0,
+            current_biome: None,
+            target: None,
+            selected_block: String::new(),
             glyph_brush,
             staging_belt: wgpu::util::StagingBelt::new(1024),
         }
     }
 
-    pub fn update(&mut self, game_state: &GameState) {
-        // Update camera (same as before)
+    pub fn update(&mut self, game_state: &mut GameState) {
+        // Fog hides the edge of the loaded square of chunks, while the far plane reaches
+        // its corners so nothing loaded is ever clipped
+        let view_distance = (game_state.render_distance() * CHUNK_SIZE) as f32;
+        self.camera.set_far(view_distance * std::f32::consts::SQRT_2);
+        self.camera_uniform.fog_end = view_distance;
+
         self.update_camera(
             game_state.camera_position(),
             game_state.camera_direction(),
             game_state.camera_up()
         );
 
-        self.camera_uniform.camera_pos = [
-            game_state.camera_position().x,
-            game_state.camera_position().y,
-            game_state.camera_position().z,
-        ];
-
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform])
-        );
-
         // Only update chunks that were modified
         if game_state.chunks_updated() {
-            for (pos, chunk) in game_state.chunks() {
-                if !self.chunk_meshes.contains_key(pos) {
-                    // Generate mesh for new chunk
-                    let vertices = MeshGenerator::generate_chunk_mesh(
-                        &chunk.voxels,
-                        chunk.chunk_x,
-                        chunk.chunk_z
-                    );
-
-                    if !vertices.is_empty() {
-                        let chunk_mesh = ChunkMesh::new(&self.device, &vertices);
-                        self.chunk_meshes.insert(*pos, chunk_mesh);
+            // Remove meshes for unloaded chunks and stop meshing them
+            let world = game_state.world();
+            self.chunk_meshes.retain(|pos, _| world.contains_chunk(*pos));
+            self.pending_meshes.retain(|pos, pending| {
+                let loaded = world.contains_chunk(*pos);
+                if !loaded {
+                    pending.job.cancel();
+                }
+                loaded
+            });
+
+            let new_chunks: Vec<ChunkPos> = world
+                .chunks()
+                .map(|chunk| chunk.pos)
+                .filter(|pos| {
+                    !self.chunk_meshes.contains_key(pos) && !self.pending_meshes.contains_key(pos)
+                })
+                .collect();
+
+            // Chunks next to a new chunk were meshed with their shared border exposed, so
+            // they have to be rebuilt now that the faces along it can be culled
+            let mut chunks_to_mesh: HashSet<ChunkPos> = new_chunks.iter().cloned().collect();
+            for pos in new_chunks {
+                for neighbor in pos.neighbors() {
+                    if
+                        self.chunk_meshes.contains_key(&neighbor) ||
+                        self.pending_meshes.contains_key(&neighbor)
+                    {
+                        chunks_to_mesh.insert(neighbor);
                     }
                 }
             }
 
-            // Remove meshes for unloaded chunks
-            self.chunk_meshes.retain(|pos, _| game_state.chunks().contains_key(pos));
+            for pos in chunks_to_mesh {
+                self.queue_mesh(game_state, pos, None);
+            }
+
+            self.loaded_chunks = world.chunk_count();
+            self.average_chunk_memory = game_state.average_chunk_memory();
         }
 
+        // Rebuild the meshes of chunks that were edited since the last frame. Chunks without
+        // a mesh yet are picked up with their edits once they are meshed for the first time.
+        let edited: Vec<ChunkPos> = game_state
+            .take_changed_chunks()
+            .into_iter()
+            .filter(|pos| {
+                self.chunk_meshes.contains_key(pos) || self.pending_meshes.contains_key(pos)
+            })
+            .collect();
+        if !edited.is_empty() {
+            self.edit_count += 1;
+            for pos in edited {
+                self.queue_mesh(game_state, pos, Some(self.edit_count));
+            }
+        }
+
+        self.queued_chunks = game_state.queued_chunks();
+        self.current_biome = game_state.current_biome();
+        self.target = game_state.target().map(|hit| {
+            (game_state.block_registry().get(hit.block).name.clone(), hit.pos)
+        });
+        if let Some((_, pos)) = &self.target {
+            let outline = OutlineVertex::block_outline(*pos);
+            self.queue.write_buffer(&self.outline_buffer, 0, bytemuck::cast_slice(&outline));
+        }
+        let selected = game_state.block_registry().get(game_state.selected_block());
+        self.selected_block.clone_from(&selected.name);
+        self.upload_finished_meshes(game_state);
+
         // Update FPS display
         self.fps_display.update();
     }
 
+    // Hands a chunk to the meshing workers, replacing any older job for the same chunk.
+    // When the replaced job was part of an edit, that edit and the new one are merged, so
+    // the other chunks of the old edit wait for this chunk's new mesh too.
+    fn queue_mesh(&mut self, game_state: &GameState, pos: ChunkPos, mut edit: Option<u64>) {
+        if let Some(pending) = self.pending_meshes.remove(&pos) {
+            pending.job.cancel();
+            match (pending.edit, edit) {
+                (Some(old), Some(new)) if old != new => {
+                    for other in self.pending_meshes.values_mut() {
+                        if other.edit == Some(old) {
+                            other.edit = Some(new);
+                        }
+                    }
+                }
+                (Some(old), None) => {
+                    edit = Some(old);
+                }
+                _ => {}
+            }
+        }
+
+        let job = self.mesh_pool.submit(MeshJob {
+            chunk: Arc::clone(game_state.world().chunk(pos).unwrap()),
+            neighbors: ChunkNeighbors::from_world(game_state.world(), pos),
+            registry: Arc::clone(game_state.block_registry()),
+            mode: self.meshing_mode,
+        });
+        self.pending_meshes.insert(pos, PendingMesh { job, edit });
+    }
+
+    // Moves meshes the workers are done with onto the GPU, nearest first and no more than
+    // the upload budget allows per frame. The old mesh of a chunk stays visible until its
+    // replacement is uploaded.
+    fn upload_finished_meshes(&mut self, game_state: &GameState) {
+        self.ready_meshes.extend(self.mesh_pool.results());
+
+        // Results of cancelled or replaced jobs are stale
+        let pending_meshes = &self.pending_meshes;
+        self.ready_meshes.retain(|(id, result)| {
+            pending_meshes
+                .get(&result.pos)
+                .is_some_and(|pending| pending.job.id == *id)
+        });
+
+        let (edited, mut loaded): (Vec<_>, Vec<_>) = std::mem
+            ::take(&mut self.ready_meshes)
+            .into_iter()
+            .partition(|(_, result)| pending_meshes[&result.pos].edit.is_some());
+
+        // An edit is uploaded all at once when every one of its meshes is ready, outside of
+        // the budget so edits show up right away
+        let mut ready_counts: HashMap<u64, usize> = HashMap::new();
+        for (_, result) in &edited {
+            *ready_counts.entry(pending_meshes[&result.pos].edit.unwrap()).or_default() += 1;
+        }
+        let complete: HashSet<u64> = ready_counts
+            .into_iter()
+            .filter(|(edit, ready)| {
+                let total = pending_meshes
+                    .values()
+                    .filter(|pending| pending.edit == Some(*edit))
+                    .count();
+                total == *ready
+            })
+            .map(|(edit, _)| edit)
+            .collect();
+        let (complete_edits, waiting_edits): (Vec<_>, Vec<_>) = edited
+            .into_iter()
+            .partition(|(_, result)| complete.contains(&pending_meshes[&result.pos].edit.unwrap()));
+        for (_, result) in complete_edits {
+            self.upload_mesh(result);
+        }
+
+        // Sorted so the most important mesh is at the end, ready to be popped
+        loaded.sort_by(|(_, a), (_, b)| {
+            let a = game_state.chunk_priority(a.pos);
+            let b = game_state.chunk_priority(b.pos);
+            b.total_cmp(&a)
+        });
+
+        for _ in 0..game_state.load_budget().uploads_per_frame {
+            let Some((_, result)) = loaded.pop() else {
+                break;
+            };
+            self.upload_mesh(result);
+        }
+
+        self.ready_meshes = waiting_edits;
+        self.ready_meshes.extend(loaded);
+    }
+
+    // Replaces the chunk's mesh, the old one is drawn right up until this point
+    fn upload_mesh(&mut self, result: MeshResult) {
+        self.pending_meshes.remove(&result.pos);
+
+        // Empty meshes are kept too so the chunk isn't treated as new again
+        let chunk_mesh = ChunkMesh::new(&self.device, &result.vertices, result.pos);
+        self.quad_index_buffer.reserve(&self.device, chunk_mesh.num_quads);
+        self.chunk_meshes.insert(result.pos, chunk_mesh);
+    }
+
+    // Switches between greedy and per-face meshing and rebuilds every loaded chunk
+    pub fn toggle_meshing_mode(&mut self, game_state: &GameState) {
+        self.meshing_mode = match self.meshing_mode {
+            MeshingMode::Naive => MeshingMode::Greedy,
+            MeshingMode::Greedy => MeshingMode::Naive,
+        };
+
+        let loaded: Vec<ChunkPos> = self.chunk_meshes
+            .keys()
+            .chain(self.pending_meshes.keys())
+            .cloned()
+            .collect();
+        for pos in loaded {
+            self.queue_mesh(game_state, pos, None);
+        }
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
@@ -317,14 +625,10 @@ impl RenderState {
         up: Vector3<f32>
     ) {
         // Update camera uniform with new camera data
-        let view = Matrix4::look_to_rh(position, direction, up);
-        let proj = perspective(
-            Deg(45.0),
-            (self.size.width as f32) / (self.size.height as f32),
-            0.1,
-            100.0
-        );
-        self.camera_uniform.view_proj = (OPENGL_TO_WGPU_MATRIX * proj * view).into();
+        self.camera.position = position;
+        self.camera.direction = direction;
+        self.camera.up = up;
+        self.camera_uniform.update_view_proj(&self.camera);
 
         // Update GPU buffer
         self.queue.write_buffer(
@@ -366,7 +670,7 @@ impl RenderState {
                     depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                         view: &self.depth_texture,
                         depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
+                            load: wgpu::LoadOp::Clear(self.camera.depth_clear_value()),
                             store: true,
                         }),
                         stencil_ops: None,
@@ -376,11 +680,34 @@ impl RenderState {
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_index_buffer(
+                self.quad_index_buffer.buffer.slice(..),
+                wgpu::IndexFormat::Uint32
+            );
 
-            // Render each chunk separately
+            // Render each chunk separately, skipping the ones outside the view
+            let frustum = Frustum::from_view_projection(&self.camera_uniform.view_proj.into());
+            self.drawn_chunks = 0;
+            self.culled_chunks = 0;
             for chunk_mesh in self.chunk_meshes.values() {
+                if chunk_mesh.num_vertices == 0 {
+                    continue;
+                }
+                if !frustum.intersects_aabb(&chunk_mesh.bounds) {
+                    self.culled_chunks += 1;
+                    continue;
+                }
+                self.drawn_chunks += 1;
                 render_pass.set_vertex_buffer(0, chunk_mesh.vertex_buffer.slice(..));
-                render_pass.draw(0..chunk_mesh.num_vertices, 0..1);
+                render_pass.set_vertex_buffer(1, chunk_mesh.instance_buffer.slice(..));
+                render_pass.draw_indexed(0..chunk_mesh.num_quads * 6, 0, 0..1);
+            }
+
+            // The outline goes last so it's blended over the terrain
+            if self.target.is_some() {
+                render_pass.set_pipeline(&self.outline_pipeline);
+                render_pass.set_vertex_buffer(0, self.outline_buffer.slice(..));
+                render_pass.draw(0..OutlineVertex::COUNT as u32, 0..1);
             }
         }
 
@@ -394,16 +721,70 @@ impl RenderState {
     fn render_fps_display(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
         let scale = ((self.size.height as f32) / 40.0).round();
 
+        let total_quads: u32 = self.chunk_meshes
+            .values()
+            .map(|mesh| mesh.num_quads)
+            .sum();
+        let total_vertices: u32 = self.chunk_meshes
+            .values()
+            .map(|mesh| mesh.num_vertices)
+            .sum();
+
         self.glyph_brush.queue(Section {
             screen_position: ((self.size.width as f32) - 10.0, 10.0),
             bounds: (self.size.width as f32, self.size.height as f32),
             text: vec![
                 Text::new(&format!("FPS: {}", self.fps_display.fps()))
                     .with_color([1.0, 1.0, 1.0, 1.0])
-                    .with_scale(scale)
+                    .with_scale(scale),
+                Text::new(
+                    &format!(
+                        "\nChunks: {} ({:.1} KiB each), {} queued",
+                        self.loaded_chunks,
+                        (self.average_chunk_memory as f32) / 1024.0,
+                        self.queued_chunks
+                    )
+                )
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(scale * 0.5),
+                Text::new(
+                    &format!(
+                        "\nMesh ({:?}): {} quads, {} vertices",
+                        self.meshing_mode,
+                        total_quads,
+                        total_vertices
+                    )
+                )
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(scale * 0.5),
+                Text::new(
+                    &format!("\nDrawn: {} chunks, culled: {}", self.drawn_chunks, self.culled_chunks)
+                )
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(scale * 0.5),
+                Text::new(
+                    &(match self.current_biome {
+                        Some(biome) => format!("\nBiome: {:?}", biome),
+                        None => String::new(),
+                    })
+                )
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(scale * 0.5),
+                Text::new(
+                    &(match &self.target {
+                        Some((name, pos)) => {
+                            format!("\nTarget: {} at ({}, {}, {})", name, pos.x, pos.y, pos.z)
+                        }
+                        None => String::new(),
+                    })
+                )
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(scale * 0.5),
+                Text::new(&format!("\nSelected: {}", self.selected_block))
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(scale * 0.5)
             ],
             layout: wgpu_glyph::Layout::default().h_align(wgpu_glyph::HorizontalAlign::Right),
-            ..Section::default()
         });
 
         self.glyph_brush
@@ -420,6 +801,66 @@ impl RenderState {
         self.staging_belt.finish();
     }
 
+    // Draws lines over the terrain without writing depth. The vertex shader moves the lines
+    // slightly towards the camera so they stay in front of the faces they lie on. Pipeline
+    // depth bias wouldn't help there, it only applies to triangles.
+    fn create_outline_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        camera: &Camera
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../outline.wgsl").into()),
+        });
+
+        device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some("Outline Pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[OutlineVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: camera.depth_compare(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            })
+        )
+    }
+
     fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration
@@ -1,24 +1,50 @@
-use wgpu;
 use bytemuck::{ Pod, Zeroable };
 
+use crate::terrain::biome::Biome;
+use crate::terrain::block::BlockId;
+use crate::terrain::position::WorldPos;
+
+// Chunk vertices are packed into 8 bytes. The first word holds the corner position
+// relative to the chunk origin, the index of the face normal and the biome for tinting:
+//   bits 0-4: x (0..=16), bits 5-13: y (0..=256), bits 14-18: z (0..=16), bits 19-21: normal,
+//   bits 22-24: biome
+// The second word holds the block id, which the shader uses to look up the color.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Vertex {
-    pub position: [f32; 3],
-    pub color: [f32; 3],
-    pub normal: [f32; 3],
+    pub position_normal: u32,
+    pub block: u32,
 }
 
 impl Vertex {
     pub const VERTEX_LAYOUT: [
         wgpu::VertexAttribute;
-        3
+        2
     ] = wgpu::vertex_attr_array![
-            0 => Float32x3,  // position
-            1 => Float32x3,  // color
-            2 => Float32x3,  // normal
+            0 => Uint32,  // position and normal
+            1 => Uint32,  // block
         ];
 
+    pub fn new(position: [u32; 3], normal: u32, block: BlockId, biome: Biome) -> Self {
+        Self {
+            position_normal: position[0] |
+                (position[1] << 5) |
+                (position[2] << 14) |
+                (normal << 19) |
+                ((biome as u32) << 22),
+            block: block.0 as u32,
+        }
+    }
+
+    // Unpacks the corner position relative to the chunk origin
+    pub fn local_position(&self) -> [u32; 3] {
+        [
+            self.position_normal & 31,
+            (self.position_normal >> 5) & 511,
+            (self.position_normal >> 14) & 31,
+        ]
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -27,3 +53,87 @@ impl Vertex {
         }
     }
 }
+
+// Per chunk data, bound as a single instance so the shader can place the chunk's
+// local vertex positions in the world
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ChunkInstance {
+    pub origin: [i32; 3],
+}
+
+impl ChunkInstance {
+    pub const INSTANCE_LAYOUT: [
+        wgpu::VertexAttribute;
+        1
+    ] = wgpu::vertex_attr_array![
+            2 => Sint32x3,  // chunk origin
+        ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ChunkInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::INSTANCE_LAYOUT,
+        }
+    }
+}
+
+// A corner of the outline drawn around the targeted block, in world space
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct OutlineVertex {
+    pub position: [f32; 3],
+}
+
+impl OutlineVertex {
+    pub const VERTEX_LAYOUT: [
+        wgpu::VertexAttribute;
+        1
+    ] = wgpu::vertex_attr_array![
+            0 => Float32x3,  // position
+        ];
+
+    // Number of vertices in an outline, two for each of the 12 edges of a block
+    pub const COUNT: usize = 24;
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OutlineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::VERTEX_LAYOUT,
+        }
+    }
+
+    // The edges of a block as a line list. The box is a little larger than the block, so
+    // the lines aren't half hidden inside the faces next to them.
+    pub fn block_outline(pos: WorldPos) -> [OutlineVertex; Self::COUNT] {
+        const MARGIN: f32 = 0.002;
+        let min = [pos.x as f32 - MARGIN, pos.y as f32 - MARGIN, pos.z as f32 - MARGIN];
+        let max = min.map(|value| value + 1.0 + 2.0 * MARGIN);
+        let corner = |x: usize, y: usize, z: usize| -> OutlineVertex {
+            OutlineVertex {
+                position: [[min[0], max[0]][x], [min[1], max[1]][y], [min[2], max[2]][z]],
+            }
+        };
+
+        let mut vertices = [corner(0, 0, 0); Self::COUNT];
+        let mut index = 0;
+        // Every edge runs along one axis, from a corner at the low end of that axis
+        for axis in 0..3 {
+            for a in 0..2 {
+                for b in 0..2 {
+                    let (start, end) = match axis {
+                        0 => (corner(0, a, b), corner(1, a, b)),
+                        1 => (corner(a, 0, b), corner(a, 1, b)),
+                        _ => (corner(a, b, 0), corner(a, b, 1)),
+                    };
+                    vertices[index] = start;
+                    vertices[index + 1] = end;
+                    index += 2;
+                }
+            }
+        }
+        vertices
+    }
+}
@@ -1,11 +1,22 @@
 struct CameraUniform {
     view_proj: mat4x4<f32>,
     camera_pos: vec3<f32>,
+    fog_end: f32,
+    depth_bias: f32,
 };
 
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// The alpha of a block color says whether the block is tinted by its biome
+struct BlockColors {
+    colors: array<vec4<f32>, 256>,
+    biome_tints: array<vec4<f32>, 8>,
+};
+
+@group(0) @binding(1)
+var<uniform> block_colors: BlockColors;
+
 struct LightData {
     direction: vec3<f32>,
     color: vec3<f32>,
@@ -13,10 +24,14 @@ struct LightData {
     specular_strength: f32,
 };
 
+// See Vertex in vertex.rs for the packing layout
 struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) color: vec3<f32>,
-    @location(2) normal: vec3<f32>,
+    @location(0) position_normal: u32,
+    @location(1) block: u32,
+};
+
+struct ChunkInput {
+    @location(2) origin: vec3<i32>,
 };
 
 struct VertexOutput {
@@ -24,20 +39,37 @@ struct VertexOutput {
     @location(0) color: vec3<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) world_position: vec3<f32>,
-    @location(3) view_position: vec3<f32>,
 };
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(model: VertexInput, chunk: ChunkInput) -> VertexOutput {
+    // Indexed in the same order as Face in mesh_generator.rs
+    var normals = array<vec3<f32>, 6>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(-1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, -1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 0.0, -1.0),
+    );
+
+    let local_position = vec3<f32>(
+        f32(model.position_normal & 31u),
+        f32((model.position_normal >> 5u) & 511u),
+        f32((model.position_normal >> 14u) & 31u),
+    );
+    let normal_index = (model.position_normal >> 19u) & 7u;
+    let biome = (model.position_normal >> 22u) & 7u;
+    let position = vec3<f32>(chunk.origin) + local_position;
+
     var out: VertexOutput;
-    let world_position = vec4<f32>(model.position, 1.0);
+    let world_position = vec4<f32>(position, 1.0);
     out.clip_position = camera.view_proj * world_position;
-    out.world_position = model.position;
-    out.world_normal = model.normal;
-    out.color = model.color;
-    
-    // Calculate view space position for fog
-    out.view_position = (camera.view_proj * world_position).xyz;
+    out.world_position = position;
+    out.world_normal = normals[normal_index];
+    let block_color = block_colors.colors[model.block];
+    let tint = mix(vec3<f32>(1.0), block_colors.biome_tints[biome].rgb, block_color.a);
+    out.color = block_color.rgb * tint;
     
     return out;
 }
@@ -87,11 +119,10 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // Add rim lighting
     result = result + (rim * rim_color);
     
-    // Calculate fog
+    // Calculate fog, fading out by the edge of the loaded world
     let fog_color = vec3<f32>(0.6, 0.7, 0.8);
-    let fog_start = 30.0;
-    let fog_end = 200.0;
-    let fog_amount = calculate_fog(length(in.view_position), fog_start, fog_end);
+    let fog_start = camera.fog_end * 0.3;
+    let fog_amount = calculate_fog(distance(camera.camera_pos, in.world_position), fog_start, camera.fog_end);
     
     // Mix final color with fog
     result = mix(result, fog_color, fog_amount);
@@ -0,0 +1,134 @@
+use super::block::BlockId;
+use super::config::TerrainConfig;
+use super::fractal::FractalNoise;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Biome {
+    #[default]
+    Plains,
+    Desert,
+    Mountains,
+    Tundra,
+    Forest,
+    Ocean,
+}
+
+impl Biome {
+    // Every biome, in the order used by the shader's tint table
+    pub const ALL: [Biome; 6] = [
+        Biome::Plains,
+        Biome::Desert,
+        Biome::Mountains,
+        Biome::Tundra,
+        Biome::Forest,
+        Biome::Ocean,
+    ];
+
+    // Where the biome sits in (temperature, humidity) space, both in [-1, 1]. Each
+    // position gets the biome whose climate is closest to its own.
+    fn climate(self) -> (f64, f64) {
+        match self {
+            Biome::Plains => (0.2, -0.1),
+            Biome::Desert => (0.7, -0.6),
+            Biome::Mountains => (-0.2, -0.5),
+            Biome::Tundra => (-0.6, 0.0),
+            Biome::Forest => (0.2, 0.4),
+            Biome::Ocean => (0.0, 0.8),
+        }
+    }
+
+    // Maps a terrain noise value in [-1, 1] to a height above the base height, in
+    // multiples of the configured amplitude
+    pub fn height(self, noise_value: f64) -> f64 {
+        match self {
+            Biome::Plains => 0.1 + noise_value * 0.25,
+            Biome::Desert => 0.1 + noise_value * 0.4,
+            // Ridged, so the peaks are sharp and the valleys wide
+            Biome::Mountains => 0.5 + (1.0 - noise_value.abs()).powi(2) * 2.0,
+            Biome::Tundra => 0.3 + noise_value * 0.5,
+            Biome::Forest => 0.2 + noise_value * 0.6,
+            Biome::Ocean => -0.8 + noise_value * 0.3,
+        }
+    }
+
+    // The top block of the ground
+    pub fn surface_block(self) -> BlockId {
+        match self {
+            Biome::Plains | Biome::Forest => BlockId::GRASS,
+            Biome::Desert | Biome::Ocean => BlockId::SAND,
+            Biome::Mountains => BlockId::STONE,
+            Biome::Tundra => BlockId::SNOW,
+        }
+    }
+
+    // The few blocks between the surface and the stone
+    pub fn filler_block(self) -> BlockId {
+        match self {
+            Biome::Plains | Biome::Forest | Biome::Tundra => BlockId::DIRT,
+            Biome::Desert | Biome::Ocean => BlockId::SAND,
+            Biome::Mountains => BlockId::STONE,
+        }
+    }
+
+    // Multiplied onto the color of tinted blocks such as grass
+    pub fn tint(self) -> [f32; 3] {
+        match self {
+            Biome::Plains => [1.0, 1.0, 1.0],
+            Biome::Desert => [1.25, 1.05, 0.6],
+            Biome::Mountains => [0.85, 0.95, 0.9],
+            Biome::Tundra => [0.85, 0.95, 1.1],
+            Biome::Forest => [0.7, 0.9, 0.6],
+            Biome::Ocean => [0.9, 1.0, 1.05],
+        }
+    }
+}
+
+// Picks biomes from two slowly changing noise fields, temperature and humidity
+pub struct BiomeMap {
+    temperature: FractalNoise,
+    humidity: FractalNoise,
+    blend: f64,
+}
+
+impl BiomeMap {
+    pub fn new(seed: u32, config: &TerrainConfig) -> Self {
+        Self {
+            temperature: FractalNoise::new(seed.wrapping_add(0x3000), config, config.biome_frequency),
+            humidity: FractalNoise::new(seed.wrapping_add(0x4000), config, config.biome_frequency),
+            blend: config.biome_blend.max(1.0e-3),
+        }
+    }
+
+    fn climate_at(&self, x: i32, z: i32) -> (f64, f64) {
+        // Fractal noise rarely gets near its limits, so stretch it to cover the climate space
+        let temperature = (self.temperature.get(x as f64, z as f64) * 2.0).clamp(-1.0, 1.0);
+        let humidity = (self.humidity.get(x as f64, z as f64) * 2.0).clamp(-1.0, 1.0);
+        (temperature, humidity)
+    }
+
+    // How much each biome in Biome::ALL contributes at a position, adding up to one. Away
+    // from borders the nearest biome has almost all the weight, near a border it is shared
+    // with its neighbors so heights change smoothly from one biome to the next.
+    pub fn weights(&self, x: i32, z: i32) -> [f64; 6] {
+        let (temperature, humidity) = self.climate_at(x, z);
+        let distances = Biome::ALL.map(|biome| {
+            let (biome_temperature, biome_humidity) = biome.climate();
+            (temperature - biome_temperature).powi(2) + (humidity - biome_humidity).powi(2)
+        });
+
+        // Measured relative to the nearest biome, so the exponent can't underflow to zero
+        let nearest = distances.iter().copied().fold(f64::MAX, f64::min);
+        let mut weights = distances.map(|distance| (-(distance - nearest) / (self.blend * self.blend)).exp());
+        let total: f64 = weights.iter().sum();
+        for weight in &mut weights {
+            *weight /= total;
+        }
+        weights
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let weights = self.weights(x, z);
+        let nearest = (0..weights.len()).max_by(|&a, &b| weights[a].total_cmp(&weights[b])).unwrap();
+        Biome::ALL[nearest]
+    }
+}
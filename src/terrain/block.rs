@@ -0,0 +1,108 @@
+// Identifies a block type. The numeric value is an index into the BlockRegistry.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockId(pub u16);
+
+impl BlockId {
+    pub const AIR: BlockId = BlockId(0);
+    pub const STONE: BlockId = BlockId(1);
+    pub const DIRT: BlockId = BlockId(2);
+    pub const GRASS: BlockId = BlockId(3);
+    pub const SAND: BlockId = BlockId(4);
+    pub const SNOW: BlockId = BlockId(5);
+    pub const WATER: BlockId = BlockId(6);
+    pub const DEEPSLATE: BlockId = BlockId(7);
+    pub const LOG: BlockId = BlockId(8);
+    pub const LEAVES: BlockId = BlockId(9);
+    pub const COBBLESTONE: BlockId = BlockId(10);
+
+    pub fn is_air(self) -> bool {
+        self == Self::AIR
+    }
+}
+
+pub struct Block {
+    pub name: String,
+    pub color: [f32; 3],
+    // Solid blocks are the ones the player collides with and can target
+    pub solid: bool,
+    // Transparent blocks don't hide the faces of the blocks next to them
+    pub transparent: bool,
+    // Tinted blocks have their color multiplied by the tint of the biome they are in
+    pub tinted: bool,
+}
+
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+}
+
+impl BlockRegistry {
+    pub fn new() -> Self {
+        Self { blocks: Vec::new() }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &str,
+        color: [f32; 3],
+        solid: bool,
+        transparent: bool,
+        tinted: bool
+    ) -> BlockId {
+        let id = BlockId(self.blocks.len() as u16);
+        self.blocks.push(Block {
+            name: name.to_string(),
+            color,
+            solid,
+            transparent,
+            tinted,
+        });
+        id
+    }
+
+    pub fn get(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0 as usize]
+    }
+
+    pub fn find(&self, name: &str) -> Option<BlockId> {
+        self.blocks
+            .iter()
+            .position(|block| block.name == name)
+            .map(|index| BlockId(index as u16))
+    }
+
+    // Opaque blocks completely hide the faces of the blocks touching them
+    pub fn is_opaque(&self, id: BlockId) -> bool {
+        let block = self.get(id);
+        block.solid && !block.transparent
+    }
+
+    // All registered blocks, the index of each one is its BlockId
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+}
+
+impl Default for BlockRegistry {
+    // Registers the built-in blocks in the same order as the BlockId constants, followed
+    // by the ones that are only looked up by name
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("air", [0.0, 0.0, 0.0], false, true, false);
+        registry.register("stone", [0.5, 0.5, 0.5], true, false, false);
+        registry.register("dirt", [0.45, 0.32, 0.2], true, false, false);
+        registry.register("grass", [0.3, 0.5, 0.2], true, false, true);
+        registry.register("sand", [0.7, 0.7, 0.3], true, false, false);
+        registry.register("snow", [0.9, 0.92, 0.95], true, false, false);
+        registry.register("water", [0.2, 0.4, 0.75], false, true, false);
+        registry.register("deepslate", [0.3, 0.3, 0.33], true, false, false);
+        registry.register("log", [0.4, 0.3, 0.2], true, false, false);
+        registry.register("leaves", [0.25, 0.5, 0.2], true, true, true);
+        registry.register("cobblestone", [0.42, 0.42, 0.42], true, false, false);
+        registry.register("bedrock", [0.15, 0.15, 0.15], true, false, false);
+        registry.register("coal_ore", [0.2, 0.2, 0.2], true, false, false);
+        registry.register("iron_ore", [0.75, 0.6, 0.5], true, false, false);
+        registry.register("gold_ore", [0.9, 0.8, 0.2], true, false, false);
+        registry.register("diamond_ore", [0.4, 0.9, 0.9], true, false, false);
+        registry
+    }
+}
@@ -0,0 +1,192 @@
+use std::f64::consts::TAU;
+
+use super::block::BlockId;
+use super::chunk::{ Chunk, CHUNK_HEIGHT, CHUNK_SIZE };
+use super::config::{ CaveConfig, TerrainConfig };
+use super::fractal::FractalNoise;
+use super::position::ChunkPos;
+use super::random::{ self, Rng };
+
+// Carves caves out of an already generated chunk. Cheese caves come from 3D noise, worm
+// caves are tunnels following a random walk. Each worm belongs to the chunk it starts in
+// and is rebuilt from that chunk's seed whenever a chunk it passes through is generated,
+// so tunnels line up across chunk borders no matter which chunk was generated first.
+pub struct CaveCarver {
+    seed: u32,
+    cheese_noise: FractalNoise,
+    config: CaveConfig,
+}
+
+// One step of a worm, everything closer than the radius to the center is carved out
+#[derive(Copy, Clone)]
+pub struct WormStep {
+    center: [f64; 3],
+    radius: f64,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u32, config: &TerrainConfig) -> Self {
+        Self {
+            seed,
+            cheese_noise: FractalNoise::new(seed.wrapping_add(0x2000), config, config.caves.cheese_frequency),
+            config: config.caves.clone(),
+        }
+    }
+
+    pub fn carve(&self, chunk: &mut Chunk) {
+        if !self.config.enabled {
+            return;
+        }
+        self.carve_cheese(chunk);
+        self.carve_worms(chunk);
+    }
+
+    // The bottom layer is never carved so there's always a floor to the world
+    fn carve_range(&self) -> (i32, i32) {
+        (self.config.min_height.max(1), self.config.max_height.min(CHUNK_HEIGHT - 1))
+    }
+
+    fn carve_cheese(&self, chunk: &mut Chunk) {
+        let (min_y, max_y) = self.carve_range();
+        let origin = chunk.pos.origin();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in min_y..=max_y {
+                    if chunk.get(x, y, z).is_air() {
+                        continue;
+                    }
+                    let noise_value = self.cheese_noise.get3(
+                        (origin.x + x) as f64,
+                        y as f64,
+                        (origin.z + z) as f64
+                    );
+                    if noise_value > self.config.cheese_threshold {
+                        chunk.set(x, y, z, BlockId::AIR);
+                    }
+                }
+            }
+        }
+    }
+
+    // Whether the caves carve out a block of ground. Unlike carve this works anywhere
+    // without generating the chunk, with the worms taken from worms_near for an area
+    // that contains the block.
+    pub fn is_carved(&self, [x, y, z]: [i32; 3], worms: &[WormStep]) -> bool {
+        if !self.config.enabled || !(1..CHUNK_HEIGHT).contains(&y) {
+            return false;
+        }
+        let (min_y, max_y) = self.carve_range();
+        if (min_y..=max_y).contains(&y) {
+            let noise_value = self.cheese_noise.get3(x as f64, y as f64, z as f64);
+            if noise_value > self.config.cheese_threshold {
+                return true;
+            }
+        }
+        worms.iter().any(|step| {
+            let dx = (x as f64) + 0.5 - step.center[0];
+            let dy = (y as f64) + 0.5 - step.center[1];
+            let dz = (z as f64) + 0.5 - step.center[2];
+            dx * dx + dy * dy + dz * dz < step.radius * step.radius
+        })
+    }
+
+    // The worm steps that reach within distance blocks of a chunk, see is_carved
+    pub fn worms_near(&self, pos: ChunkPos, distance: i32) -> Vec<WormStep> {
+        let mut worms = Vec::new();
+        if !self.config.enabled {
+            return worms;
+        }
+        let origin = pos.origin();
+        let min = [(origin.x - distance) as f64, (origin.z - distance) as f64];
+        let max = [(origin.x + CHUNK_SIZE + distance) as f64, (origin.z + CHUNK_SIZE + distance) as f64];
+        let range = self.worm_range() + (distance + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        self.walk_worms(pos, range, |step| {
+            let reaches =
+                step.center[0] + step.radius >= min[0] &&
+                step.center[0] - step.radius <= max[0] &&
+                step.center[2] + step.radius >= min[1] &&
+                step.center[2] - step.radius <= max[1];
+            if reaches {
+                worms.push(step);
+            }
+        });
+        worms
+    }
+
+    fn carve_worms(&self, chunk: &mut Chunk) {
+        self.walk_worms(chunk.pos, self.worm_range(), |step| Self::carve_sphere(chunk, step));
+    }
+
+    // Any chunk this many chunks away or closer can have worms reaching into a chunk
+    fn worm_range(&self) -> i32 {
+        let reach = (self.config.worm_length as f64) + self.config.worm_radius;
+        (reach / (CHUNK_SIZE as f64)).ceil() as i32
+    }
+
+    // Replays the worms of every chunk up to range chunks away from the center one
+    fn walk_worms(&self, center: ChunkPos, range: i32, mut visit: impl FnMut(WormStep)) {
+        for origin_x in center.x - range..=center.x + range {
+            for origin_z in center.z - range..=center.z + range {
+                let mut rng = Rng::new(random::hash3(self.seed, origin_x as i64, 0, origin_z as i64));
+                for _ in 0..rng.count(self.config.worms_per_chunk) {
+                    self.walk_worm(&mut rng, origin_x, origin_z, &mut visit);
+                }
+            }
+        }
+    }
+
+    // Walks a single worm from its start in the origin chunk. The walk takes the same
+    // random numbers whatever is done with the steps, so every chunk sees the same path.
+    fn walk_worm(&self, rng: &mut Rng, origin_x: i32, origin_z: i32, visit: &mut impl FnMut(WormStep)) {
+        let (min_y, max_y) = self.carve_range();
+        let mut position = [
+            ((origin_x * CHUNK_SIZE) as f64) + rng.range(0.0, CHUNK_SIZE as f64),
+            rng.range(min_y as f64, (max_y + 1) as f64),
+            ((origin_z * CHUNK_SIZE) as f64) + rng.range(0.0, CHUNK_SIZE as f64),
+        ];
+        let mut yaw = rng.range(0.0, TAU);
+        let mut pitch = rng.range(-0.3, 0.3);
+        let radius = self.config.worm_radius * rng.range(0.75, 1.25);
+
+        for _ in 0..self.config.worm_length {
+            position[0] += yaw.cos() * pitch.cos();
+            position[1] += pitch.sin();
+            position[2] += yaw.sin() * pitch.cos();
+
+            yaw += rng.range(-0.25, 0.25);
+            // Pull the pitch back towards level so worms don't dive straight down
+            pitch = pitch * 0.8 + rng.range(-0.15, 0.15);
+
+            visit(WormStep { center: position, radius });
+        }
+    }
+
+    fn carve_sphere(chunk: &mut Chunk, WormStep { center, radius }: WormStep) {
+        let origin = chunk.pos.origin();
+        let local_x = center[0] - (origin.x as f64);
+        let local_z = center[2] - (origin.z as f64);
+
+        // Clip the sphere's bounds to the chunk, most steps of a worm miss it entirely
+        let min_x = ((local_x - radius).floor() as i32).max(0);
+        let max_x = ((local_x + radius).ceil() as i32).min(CHUNK_SIZE - 1);
+        let min_y = ((center[1] - radius).floor() as i32).max(1);
+        let max_y = ((center[1] + radius).ceil() as i32).min(CHUNK_HEIGHT - 1);
+        let min_z = ((local_z - radius).floor() as i32).max(0);
+        let max_z = ((local_z + radius).ceil() as i32).min(CHUNK_SIZE - 1);
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                for z in min_z..=max_z {
+                    // Measure from the block's center
+                    let dx = (x as f64) + 0.5 - local_x;
+                    let dy = (y as f64) + 0.5 - center[1];
+                    let dz = (z as f64) + 0.5 - local_z;
+                    if dx * dx + dy * dy + dz * dz < radius * radius {
+                        chunk.set(x, y, z, BlockId::AIR);
+                    }
+                }
+            }
+        }
+    }
+}
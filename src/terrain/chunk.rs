@@ -1,15 +1,156 @@
+use super::biome::Biome;
+use super::block::{ BlockId, BlockRegistry };
+use super::palette::PalettedStorage;
+use super::position::ChunkPos;
+
+pub const CHUNK_SIZE: i32 = 16;
+pub const CHUNK_HEIGHT: i32 = 256;
+pub const SECTION_HEIGHT: i32 = 16;
+pub const SECTION_COUNT: usize = (CHUNK_HEIGHT / SECTION_HEIGHT) as usize;
+
+const SECTION_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * SECTION_HEIGHT) as usize;
+
+// A 16x16x16 slice of a chunk. Sections that only contain air are never allocated.
+#[derive(Clone)]
+pub struct Section {
+    blocks: PalettedStorage,
+    non_air_count: u32,
+}
+
+impl Section {
+    fn new() -> Self {
+        Self {
+            blocks: PalettedStorage::new(SECTION_VOLUME, BlockId::AIR),
+            non_air_count: 0,
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockId {
+        self.blocks.get(Self::index(x, y, z))
+    }
+
+    fn set(&mut self, x: i32, y: i32, z: i32, block: BlockId) {
+        let index = Self::index(x, y, z);
+        let old = self.blocks.get(index);
+        if old.is_air() && !block.is_air() {
+            self.non_air_count += 1;
+        } else if !old.is_air() && block.is_air() {
+            self.non_air_count -= 1;
+        }
+        self.blocks.set(index, block);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.non_air_count == 0
+    }
+
+    // True when the section has no air in it at all
+    pub fn is_full(&self) -> bool {
+        self.non_air_count as usize == SECTION_VOLUME
+    }
+
+    // True when every block of the section is opaque, so anything inside it is hidden
+    pub fn is_opaque(&self, registry: &BlockRegistry) -> bool {
+        self.is_full() && self.blocks.blocks().all(|block| registry.is_opaque(block))
+    }
+
+    fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>() - std::mem::size_of::<PalettedStorage>() +
+            self.blocks.memory_usage()
+    }
+
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (x + z * CHUNK_SIZE + y * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+}
+
+#[derive(Clone)]
 pub struct Chunk {
-    pub voxels: Vec<bool>,
-    pub chunk_x: i32,
-    pub chunk_z: i32,
+    sections: Vec<Option<Box<Section>>>,
+    // The biome of each column, indexed by x + z * CHUNK_SIZE
+    biomes: Vec<Biome>,
+    pub pos: ChunkPos,
 }
 
 impl Chunk {
-    pub fn new(voxels: Vec<bool>, chunk_x: i32, chunk_z: i32) -> Self {
+    // Creates a chunk filled with air
+    pub fn new(pos: ChunkPos) -> Self {
         Self {
-            voxels,
-            chunk_x,
-            chunk_z,
+            sections: (0..SECTION_COUNT).map(|_| None).collect(),
+            biomes: vec![Biome::default(); (CHUNK_SIZE * CHUNK_SIZE) as usize],
+            pos,
+        }
+    }
+
+    // Returns air for anything outside of the chunk
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockId {
+        if !Self::in_bounds(x, y, z) {
+            return BlockId::AIR;
+        }
+        match self.section((y / SECTION_HEIGHT) as usize) {
+            Some(section) => section.get(x, y % SECTION_HEIGHT, z),
+            None => BlockId::AIR,
         }
     }
+
+    // Writes outside of the chunk are ignored
+    pub fn set(&mut self, x: i32, y: i32, z: i32, block: BlockId) {
+        if !Self::in_bounds(x, y, z) {
+            return;
+        }
+
+        let slot = &mut self.sections[(y / SECTION_HEIGHT) as usize];
+        match slot {
+            Some(section) => {
+                section.set(x, y % SECTION_HEIGHT, z, block);
+                if section.is_empty() {
+                    *slot = None;
+                }
+            }
+            None => {
+                // Placing air into an empty section doesn't need any storage
+                if block.is_air() {
+                    return;
+                }
+                let mut section = Box::new(Section::new());
+                section.set(x, y % SECTION_HEIGHT, z, block);
+                *slot = Some(section);
+            }
+        }
+    }
+
+    pub fn biome(&self, x: i32, z: i32) -> Biome {
+        self.biomes[(x + z * CHUNK_SIZE) as usize]
+    }
+
+    pub fn set_biome(&mut self, x: i32, z: i32, biome: Biome) {
+        self.biomes[(x + z * CHUNK_SIZE) as usize] = biome;
+    }
+
+    pub fn section(&self, index: usize) -> Option<&Section> {
+        self.sections.get(index).and_then(|section| section.as_deref())
+    }
+
+    // Iterates over the sections that contain at least one non-air block
+    pub fn sections(&self) -> impl Iterator<Item = (usize, &Section)> {
+        self.sections
+            .iter()
+            .enumerate()
+            .filter_map(|(index, section)| section.as_deref().map(|section| (index, section)))
+    }
+
+    pub fn in_bounds(x: i32, y: i32, z: i32) -> bool {
+        (0..CHUNK_SIZE).contains(&x) && (0..CHUNK_HEIGHT).contains(&y) && (0..CHUNK_SIZE).contains(&z)
+    }
+
+    // Approximate number of bytes used by this chunk
+    pub fn memory_usage(&self) -> usize {
+        let sections: usize = self.sections()
+            .map(|(_, section)| section.memory_usage())
+            .sum();
+        std::mem::size_of::<Self>() +
+            self.sections.capacity() * std::mem::size_of::<Option<Box<Section>>>() +
+            self.biomes.capacity() * std::mem::size_of::<Biome>() +
+            sections
+    }
 }
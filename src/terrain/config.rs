@@ -0,0 +1,279 @@
+use serde::Deserialize;
+
+use super::biome::Biome;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseType {
+    Perlin,
+    Simplex,
+    OpenSimplex,
+    Worley,
+}
+
+// How the ground is shaped
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerrainShape {
+    // Every column is solid up to a 2D height
+    Heightmap,
+    // 3D noise is added on top of the heightmap, giving overhangs, cliffs and floating islands
+    Density,
+}
+
+// Settings for the terrain generator. Every field is optional in a config file and falls
+// back to the default below, see terrain.toml for an example.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerrainConfig {
+    pub shape: TerrainShape,
+    pub noise_type: NoiseType,
+    // Number of noise layers added on top of each other
+    pub octaves: u32,
+    // Frequency of the first octave, lower values give wider hills
+    pub frequency: f64,
+    // Frequency multiplier from one octave to the next
+    pub lacunarity: f64,
+    // Amplitude multiplier from one octave to the next
+    pub persistence: f64,
+    // Average terrain height
+    pub base_height: f64,
+    // How far the terrain goes above and below base_height
+    pub amplitude: f64,
+    // How far the terrain noise is pushed around by domain warping, in blocks. Warping
+    // bends the hills into twisting ridges instead of round blobs, 0 turns it off.
+    pub warp_strength: f64,
+    // Frequency of the noise the warping follows
+    pub warp_frequency: f64,
+    // Open air at or below this height is filled with water
+    pub sea_level: i32,
+    // Ground up to this many blocks above sea level is turned into sandy beaches
+    pub beach_height: i32,
+    // Frequency of the first octave of 3D noise for the density shape
+    pub density_frequency: f64,
+    // How far the 3D noise moves the ground away from the heightmap, in multiples of amplitude
+    pub overhang_strength: f64,
+    // Frequency of the temperature and humidity noise that biomes are picked from
+    pub biome_frequency: f64,
+    // How wide the transitions between biomes are, higher values blend more
+    pub biome_blend: f64,
+    // Stone turns into deepslate around this height
+    pub deepslate_level: i32,
+    pub erosion: ErosionConfig,
+    pub caves: CaveConfig,
+    pub ores: Vec<OreConfig>,
+    pub features: FeatureConfig,
+}
+
+// Average number of a feature in each chunk of a biome, under e.g. [features.trees]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BiomeDensity {
+    pub plains: f64,
+    pub desert: f64,
+    pub mountains: f64,
+    pub tundra: f64,
+    pub forest: f64,
+    pub ocean: f64,
+}
+
+impl BiomeDensity {
+    pub fn get(&self, biome: Biome) -> f64 {
+        match biome {
+            Biome::Plains => self.plains,
+            Biome::Desert => self.desert,
+            Biome::Mountains => self.mountains,
+            Biome::Tundra => self.tundra,
+            Biome::Forest => self.forest,
+            Biome::Ocean => self.ocean,
+        }
+    }
+
+    // The highest density of any biome
+    pub fn max(&self) -> f64 {
+        Biome::ALL.iter().map(|&biome| self.get(biome)).fold(0.0, f64::max)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    pub trees: BiomeDensity,
+    pub boulders: BiomeDensity,
+    pub ruins: BiomeDensity,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            trees: BiomeDensity {
+                plains: 0.3,
+                mountains: 0.3,
+                tundra: 0.5,
+                forest: 6.0,
+                ..BiomeDensity::default()
+            },
+            boulders: BiomeDensity {
+                plains: 0.2,
+                mountains: 1.0,
+                tundra: 0.3,
+                ..BiomeDensity::default()
+            },
+            ruins: BiomeDensity {
+                plains: 0.03,
+                desert: 0.05,
+                ..BiomeDensity::default()
+            },
+        }
+    }
+}
+
+// A kind of ore to scatter through the stone, as an [[ores]] entry in a config file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OreConfig {
+    // Name of the ore's block in the BlockRegistry
+    pub block: String,
+    // Veins start between these heights
+    pub min_height: i32,
+    pub max_height: i32,
+    // Number of blocks in each vein
+    pub vein_size: u32,
+    // Average number of veins in each chunk
+    pub veins_per_chunk: f64,
+}
+
+impl OreConfig {
+    fn new(block: &str, min_height: i32, max_height: i32, vein_size: u32, veins_per_chunk: f64) -> Self {
+        Self {
+            block: block.to_string(),
+            min_height,
+            max_height,
+            vein_size,
+            veins_per_chunk,
+        }
+    }
+}
+
+// Settings for eroding the heightmap, under [erosion] in a config file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErosionConfig {
+    pub enabled: bool,
+    // Number of water drops run over the terrain for each block of it
+    pub droplets_per_block: f64,
+    // How quickly water picks up soil, from 0 to 1
+    pub erosion_rate: f64,
+    // How quickly water drops the soil it can't carry, from 0 to 1
+    pub deposition: f64,
+    // Number of times slopes are left to slide down
+    pub thermal_iterations: u32,
+    // Height difference between neighboring blocks above which the slope slides down
+    pub talus: f64,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            droplets_per_block: 0.5,
+            erosion_rate: 0.3,
+            deposition: 0.3,
+            thermal_iterations: 8,
+            talus: 1.5,
+        }
+    }
+}
+
+// Settings for carving caves out of the ground, under [caves] in a config file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaveConfig {
+    pub enabled: bool,
+    // Caves are only carved out between these heights, although worm tunnels can wander
+    // outside of the range once they have started
+    pub min_height: i32,
+    pub max_height: i32,
+    // Frequency of the 3D noise for cheese caves, the large open caverns
+    pub cheese_frequency: f64,
+    // Noise values above this are carved out, lower values give more and bigger caverns
+    pub cheese_threshold: f64,
+    // Average number of worm tunnels starting in each chunk
+    pub worms_per_chunk: f64,
+    // Number of one block steps a worm tunnel takes
+    pub worm_length: u32,
+    // Radius of worm tunnels in blocks
+    pub worm_radius: f64,
+}
+
+impl Default for CaveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_height: 1,
+            max_height: 24,
+            cheese_frequency: 0.03,
+            cheese_threshold: 0.35,
+            worms_per_chunk: 0.4,
+            worm_length: 80,
+            worm_radius: 2.0,
+        }
+    }
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            shape: TerrainShape::Heightmap,
+            noise_type: NoiseType::Perlin,
+            octaves: 4,
+            frequency: 0.02,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            base_height: 16.0,
+            amplitude: 16.0,
+            warp_strength: 20.0,
+            warp_frequency: 0.01,
+            sea_level: 12,
+            beach_height: 2,
+            density_frequency: 0.04,
+            overhang_strength: 2.0,
+            biome_frequency: 0.002,
+            biome_blend: 0.2,
+            deepslate_level: 8,
+            erosion: ErosionConfig::default(),
+            caves: CaveConfig::default(),
+            ores: vec![
+                OreConfig::new("coal_ore", 5, 64, 12, 6.0),
+                OreConfig::new("iron_ore", 1, 40, 8, 4.0),
+                OreConfig::new("gold_ore", 1, 24, 6, 1.5),
+                OreConfig::new("diamond_ore", 1, 12, 4, 0.5)
+            ],
+            features: FeatureConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read config: {}", error),
+            ConfigError::Parse(error) => write!(f, "invalid config: {}", error),
+        }
+    }
+}
+
+impl TerrainConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&contents).map_err(ConfigError::Parse)
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{ HashMap, VecDeque };
+use std::sync::{ Arc, Mutex };
+
+use super::config::ErosionConfig;
+use super::random::{ self, Rng };
+
+// Erosion runs on square regions of the heightmap this many blocks wide
+const REGION_SIZE: i32 = 64;
+// Each region is eroded together with this many blocks of its neighbors on every side and
+// then cropped back to the region. Water flows in from the neighbors and valleys carry on
+// past the edges, so neighboring regions come out nearly the same along their shared edge.
+const MARGIN: i32 = 32;
+// Width of the area that is actually eroded for a region
+const ERODED_SIZE: i32 = REGION_SIZE + 2 * MARGIN;
+// Droplets are seeded per square cell of the world this many blocks wide, so the parts of
+// neighboring regions that overlap get the same droplets. REGION_SIZE and MARGIN are
+// multiples of it.
+const DROPLET_CELL: i32 = 16;
+// Number of eroded regions kept around
+const CACHE_SIZE: usize = 64;
+
+const NEIGHBORS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+// Heights of one region, indexed by x + z * REGION_SIZE
+type RegionHeights = Arc<Vec<f64>>;
+
+// Runs hydraulic and thermal erosion over the heightmap, one region at a time. A region
+// is always eroded the same way from the seed and the uneroded heights, so it doesn't
+// matter which chunk asks for it first. Eroded regions are cached since every chunk in a
+// region needs the same one.
+pub struct Erosion {
+    seed: u32,
+    config: ErosionConfig,
+    cache: Mutex<RegionCache>,
+}
+
+struct RegionCache {
+    regions: HashMap<(i32, i32), RegionHeights>,
+    // Oldest region first, for evicting
+    order: VecDeque<(i32, i32)>,
+}
+
+// The region heights last looked up, see Erosion::heights
+pub struct ErodedHeights<'a> {
+    erosion: &'a Erosion,
+    current: Option<((i32, i32), RegionHeights)>,
+}
+
+impl ErodedHeights<'_> {
+    // The eroded height at a position, given the function for the uneroded heights
+    pub fn get(&mut self, x: i32, z: i32, raw_height: impl Fn(i32, i32) -> f64) -> f64 {
+        let region = (x.div_euclid(REGION_SIZE), z.div_euclid(REGION_SIZE));
+        let heights = match &self.current {
+            Some((current, heights)) if *current == region => heights,
+            _ => {
+                let heights = self.erosion.region(region, raw_height);
+                &self.current.insert((region, heights)).1
+            }
+        };
+        let local_x = x.rem_euclid(REGION_SIZE);
+        let local_z = z.rem_euclid(REGION_SIZE);
+        heights[(local_x + local_z * REGION_SIZE) as usize]
+    }
+}
+
+impl Erosion {
+    pub fn new(seed: u32, config: &ErosionConfig) -> Self {
+        Self {
+            seed,
+            config: config.clone(),
+            cache: Mutex::new(RegionCache {
+                regions: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    // Looks up eroded heights, going through the shared cache only when the lookup moves
+    // on to another region. A chunk lies inside a single region, so its columns only take
+    // the cache lock once.
+    pub fn heights(&self) -> ErodedHeights<'_> {
+        ErodedHeights {
+            erosion: self,
+            current: None,
+        }
+    }
+
+    fn region(&self, region: (i32, i32), raw_height: impl Fn(i32, i32) -> f64) -> RegionHeights {
+        if let Some(heights) = self.cache.lock().unwrap().regions.get(&region) {
+            return Arc::clone(heights);
+        }
+
+        // Eroding takes a while, so it's done without holding the lock. Two threads might
+        // end up eroding the same region, but they'll get the same result.
+        let heights = Arc::new(self.erode_region(region, raw_height));
+
+        let mut cache = self.cache.lock().unwrap();
+        if !cache.regions.contains_key(&region) {
+            if cache.order.len() >= CACHE_SIZE {
+                if let Some(oldest) = cache.order.pop_front() {
+                    cache.regions.remove(&oldest);
+                }
+            }
+            cache.regions.insert(region, Arc::clone(&heights));
+            cache.order.push_back(region);
+        }
+        heights
+    }
+
+    fn erode_region(&self, region: (i32, i32), raw_height: impl Fn(i32, i32) -> f64) -> Vec<f64> {
+        // The eroded area starts MARGIN blocks before the region on both axes
+        let base_x = region.0 * REGION_SIZE - MARGIN;
+        let base_z = region.1 * REGION_SIZE - MARGIN;
+
+        let mut heights = Vec::with_capacity((ERODED_SIZE * ERODED_SIZE) as usize);
+        for z in 0..ERODED_SIZE {
+            for x in 0..ERODED_SIZE {
+                heights.push(raw_height(base_x + x, base_z + z));
+            }
+        }
+
+        self.hydraulic(&mut heights, base_x, base_z);
+        self.thermal(&mut heights);
+
+        // Crop the margin off again
+        let mut region_heights = Vec::with_capacity((REGION_SIZE * REGION_SIZE) as usize);
+        for z in MARGIN..MARGIN + REGION_SIZE {
+            let start = (MARGIN + z * ERODED_SIZE) as usize;
+            region_heights.extend_from_slice(&heights[start..start + (REGION_SIZE as usize)]);
+        }
+        region_heights
+    }
+
+    // Drops of water run downhill, picking up soil on steep slopes and leaving it behind
+    // when they slow down, which cuts valleys and fans out sediment at their bottoms
+    fn hydraulic(&self, heights: &mut [f64], base_x: i32, base_z: i32) {
+        let cells = ERODED_SIZE / DROPLET_CELL;
+        let droplets_per_cell = ((DROPLET_CELL * DROPLET_CELL) as f64) * self.config.droplets_per_block;
+        for cell_z in 0..cells {
+            for cell_x in 0..cells {
+                let world_cell_x = base_x.div_euclid(DROPLET_CELL) + cell_x;
+                let world_cell_z = base_z.div_euclid(DROPLET_CELL) + cell_z;
+                let mut rng = Rng::new(
+                    random::hash3(self.seed, world_cell_x as i64, 0, world_cell_z as i64)
+                );
+                for _ in 0..droplets_per_cell as u32 {
+                    let x = cell_x * DROPLET_CELL + rng.range_i32(0, DROPLET_CELL - 1);
+                    let z = cell_z * DROPLET_CELL + rng.range_i32(0, DROPLET_CELL - 1);
+                    self.droplet(heights, x, z);
+                }
+            }
+        }
+    }
+
+    // Runs one droplet from its starting cell until it stops or evaporates
+    fn droplet(&self, heights: &mut [f64], mut x: i32, mut z: i32) {
+        const MAX_STEPS: u32 = 48;
+        const CAPACITY: f64 = 4.0;
+        const EVAPORATION: f64 = 0.02;
+
+        let mut water = 1.0;
+        let mut speed = 1.0;
+        let mut sediment: f64 = 0.0;
+
+        for _ in 0..MAX_STEPS {
+            let index = (x + z * ERODED_SIZE) as usize;
+            let Some((next_x, next_z)) = Self::lowest_neighbor(heights, x, z) else {
+                break;
+            };
+            let next_index = (next_x + next_z * ERODED_SIZE) as usize;
+            let drop = heights[index] - heights[next_index];
+
+            if drop <= 0.0 {
+                // Stuck in a pit, fill it up with what the drop is carrying
+                heights[index] += sediment.min(-drop + 0.01);
+                break;
+            }
+
+            let capacity = drop.max(0.01) * speed * water * CAPACITY;
+            if sediment > capacity {
+                let deposit = (sediment - capacity) * self.config.deposition;
+                sediment -= deposit;
+                heights[index] += deposit;
+            } else {
+                // Never dig deeper than the next cell, or the drop would create a pit
+                let erode = ((capacity - sediment) * self.config.erosion_rate).min(drop);
+                sediment += erode;
+                heights[index] -= erode;
+            }
+
+            speed = (speed * speed + drop).sqrt();
+            water *= 1.0 - EVAPORATION;
+            x = next_x;
+            z = next_z;
+        }
+    }
+
+    fn lowest_neighbor(heights: &[f64], x: i32, z: i32) -> Option<(i32, i32)> {
+        NEIGHBORS.iter()
+            .map(|(dx, dz)| (x + dx, z + dz))
+            .filter(|&(x, z)| (0..ERODED_SIZE).contains(&x) && (0..ERODED_SIZE).contains(&z))
+            .min_by(|a, b| {
+                let a = heights[(a.0 + a.1 * ERODED_SIZE) as usize];
+                let b = heights[(b.0 + b.1 * ERODED_SIZE) as usize];
+                a.total_cmp(&b)
+            })
+    }
+
+    // Slopes steeper than the talus height slide down until they settle, which softens the
+    // cliffs left behind by the hydraulic erosion
+    fn thermal(&self, heights: &mut [f64]) {
+        const SETTLE_RATE: f64 = 0.25;
+
+        for _ in 0..self.config.thermal_iterations {
+            for z in 0..ERODED_SIZE {
+                for x in 0..ERODED_SIZE {
+                    let index = (x + z * ERODED_SIZE) as usize;
+                    for (dx, dz) in &NEIGHBORS[..4] {
+                        let (next_x, next_z) = (x + dx, z + dz);
+                        if !(0..ERODED_SIZE).contains(&next_x) || !(0..ERODED_SIZE).contains(&next_z) {
+                            continue;
+                        }
+                        let next_index = (next_x + next_z * ERODED_SIZE) as usize;
+                        let difference = heights[index] - heights[next_index];
+                        if difference > self.config.talus {
+                            let moved = (difference - self.config.talus) * SETTLE_RATE;
+                            heights[index] -= moved;
+                            heights[next_index] += moved;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use super::block::BlockId;
+use super::chunk::Chunk;
+use super::random::Rng;
+
+// A structure placed on the terrain after the ground is done, such as a tree. Features can
+// reach into neighboring chunks: when a chunk is generated every feature starting close
+// enough to it is placed again, and only the blocks inside the chunk are kept. For that to
+// line up, place must only depend on the random numbers it's given.
+pub trait Feature: Send + Sync {
+    // How far the feature can reach from its origin horizontally, in blocks
+    fn radius(&self) -> i32;
+
+    fn place(&self, writer: &mut FeatureWriter, rng: &mut Rng);
+}
+
+// Writes the blocks of a single feature into a chunk, relative to the feature's origin,
+// which is the position just above the ground it stands on
+pub struct FeatureWriter<'a> {
+    chunk: &'a mut Chunk,
+    origin: [i32; 3],
+}
+
+impl<'a> FeatureWriter<'a> {
+    pub fn new(chunk: &'a mut Chunk, origin: [i32; 3]) -> Self {
+        Self { chunk, origin }
+    }
+
+    // Features only replace air, so they don't cut into the ground or each other. Blocks
+    // outside of the chunk are dropped.
+    pub fn place(&mut self, offset: [i32; 3], block: BlockId) {
+        let origin = self.chunk.pos.origin();
+        let x = self.origin[0] + offset[0] - origin.x;
+        let y = self.origin[1] + offset[1];
+        let z = self.origin[2] + offset[2] - origin.z;
+        if self.chunk.get(x, y, z).is_air() {
+            self.chunk.set(x, y, z, block);
+        }
+    }
+}
+
+pub struct Tree;
+
+impl Feature for Tree {
+    fn radius(&self) -> i32 {
+        2
+    }
+
+    fn place(&self, writer: &mut FeatureWriter, rng: &mut Rng) {
+        let height = rng.range_i32(4, 6);
+        for dy in 0..height {
+            writer.place([0, dy, 0], BlockId::LOG);
+        }
+
+        // A round blob of leaves around the top of the trunk, with the corners missing at
+        // random so the trees don't all look the same
+        for dy in height - 2..=height + 1 {
+            let radius: i32 = if dy > height - 1 { 1 } else { 2 };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (radius == 1 || rng.next_f64() < 0.5) {
+                        continue;
+                    }
+                    writer.place([dx, dy, dz], BlockId::LEAVES);
+                }
+            }
+        }
+    }
+}
+
+pub struct Boulder;
+
+impl Feature for Boulder {
+    fn radius(&self) -> i32 {
+        2
+    }
+
+    fn place(&self, writer: &mut FeatureWriter, rng: &mut Rng) {
+        let radius = rng.range(1.0, 2.5);
+        let reach = radius.ceil() as i32;
+
+        // Sunk a block into the ground so it doesn't look like it's balancing on the grass
+        for dx in -reach..=reach {
+            for dy in -reach..=reach {
+                for dz in -reach..=reach {
+                    let distance = ((dx * dx + dy * dy + dz * dz) as f64).sqrt();
+                    if distance < radius {
+                        let block = if rng.next_f64() < 0.3 { BlockId::COBBLESTONE } else { BlockId::STONE };
+                        writer.place([dx, dy + reach - 1, dz], block);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The broken down walls of a small square building
+pub struct Ruin;
+
+impl Feature for Ruin {
+    fn radius(&self) -> i32 {
+        3
+    }
+
+    fn place(&self, writer: &mut FeatureWriter, rng: &mut Rng) {
+        let half_size = rng.range_i32(2, 3);
+        for dx in -half_size..=half_size {
+            for dz in -half_size..=half_size {
+                let is_wall = dx.abs() == half_size || dz.abs() == half_size;
+                if !is_wall {
+                    continue;
+                }
+
+                let height = rng.range_i32(0, 3);
+                for dy in 0..height {
+                    writer.place([dx, dy, dz], BlockId::COBBLESTONE);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use super::block::{ BlockId, BlockRegistry };
+use super::chunk::{ Chunk, CHUNK_HEIGHT, CHUNK_SIZE };
+use super::generator::WorldGenerator;
+use super::position::ChunkPos;
+
+#[derive(Debug)]
+pub enum PresetError {
+    // A layer that isn't in the form "count*block" or "block"
+    InvalidLayer(String),
+    UnknownBlock(String),
+    TooHigh,
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresetError::InvalidLayer(layer) => write!(f, "invalid layer \"{}\"", layer),
+            PresetError::UnknownBlock(name) => write!(f, "unknown block \"{}\"", name),
+            PresetError::TooHigh => write!(f, "layers are higher than a chunk"),
+        }
+    }
+}
+
+// The same stack of layers everywhere, described by a preset such as
+// "1*bedrock,3*dirt,1*grass" listing the layers from the bottom up
+pub struct SuperflatGenerator {
+    layers: Vec<BlockId>,
+}
+
+impl SuperflatGenerator {
+    pub const DEFAULT_PRESET: &'static str = "1*bedrock,3*dirt,1*grass";
+
+    pub fn from_preset(preset: &str, registry: &BlockRegistry) -> Result<Self, PresetError> {
+        let mut layers = Vec::new();
+        for layer in preset.split(',').map(str::trim) {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => {
+                    let count = count
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| PresetError::InvalidLayer(layer.to_string()))?;
+                    (count, name.trim())
+                }
+                None => (1, layer),
+            };
+            if name.is_empty() {
+                return Err(PresetError::InvalidLayer(layer.to_string()));
+            }
+
+            let block = registry.find(name).ok_or_else(|| PresetError::UnknownBlock(name.to_string()))?;
+            // Checked before adding the layers, so a huge count can't run out of memory
+            if count > (CHUNK_HEIGHT as usize) - layers.len() {
+                return Err(PresetError::TooHigh);
+            }
+            layers.extend(std::iter::repeat_n(block, count));
+        }
+        Ok(Self { layers })
+    }
+}
+
+impl WorldGenerator for SuperflatGenerator {
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        for (y, block) in self.layers.iter().enumerate() {
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    chunk.set(x, y as i32, z, *block);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+// Nothing but air
+pub struct VoidGenerator;
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk {
+        Chunk::new(pos)
+    }
+}
+
+// Flat ground alternating between two blocks from one chunk to the next, for checking
+// chunk borders, loading order and meshing
+pub struct CheckerboardGenerator;
+
+impl CheckerboardGenerator {
+    const HEIGHT: i32 = 16;
+}
+
+impl WorldGenerator for CheckerboardGenerator {
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk {
+        let block = if (pos.x + pos.z).rem_euclid(2) == 0 { BlockId::STONE } else { BlockId::SNOW };
+
+        let mut chunk = Chunk::new(pos);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..Self::HEIGHT {
+                    chunk.set(x, y, z, block);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_preset() {
+        let registry = BlockRegistry::default();
+        let preset = SuperflatGenerator::DEFAULT_PRESET;
+        let generator = SuperflatGenerator::from_preset(preset, &registry).unwrap();
+        assert_eq!(generator.layers.len(), 5);
+        assert_eq!(generator.layers[0], registry.find("bedrock").unwrap());
+        assert_eq!(generator.layers[1..4], [BlockId::DIRT; 3]);
+        assert_eq!(generator.layers[4], BlockId::GRASS);
+    }
+
+    #[test]
+    fn layer_without_count() {
+        let registry = BlockRegistry::default();
+        let generator = SuperflatGenerator::from_preset("2*stone, sand", &registry).unwrap();
+        assert_eq!(generator.layers, vec![BlockId::STONE, BlockId::STONE, BlockId::SAND]);
+    }
+
+    #[test]
+    fn invalid_count() {
+        let registry = BlockRegistry::default();
+        let result = SuperflatGenerator::from_preset("x*dirt", &registry);
+        assert!(matches!(result, Err(PresetError::InvalidLayer(layer)) if layer == "x*dirt"));
+    }
+
+    #[test]
+    fn unknown_block() {
+        let registry = BlockRegistry::default();
+        let result = SuperflatGenerator::from_preset("1*dirt,2*cheese", &registry);
+        assert!(matches!(result, Err(PresetError::UnknownBlock(name)) if name == "cheese"));
+    }
+
+    #[test]
+    fn too_high() {
+        let registry = BlockRegistry::default();
+        let preset = format!("{}*stone,1*grass", CHUNK_HEIGHT);
+        let result = SuperflatGenerator::from_preset(&preset, &registry);
+        assert!(matches!(result, Err(PresetError::TooHigh)));
+        let result = SuperflatGenerator::from_preset("18446744073709551615*stone", &registry);
+        assert!(matches!(result, Err(PresetError::TooHigh)));
+        let preset = format!("{}*stone", CHUNK_HEIGHT);
+        assert!(SuperflatGenerator::from_preset(&preset, &registry).is_ok());
+    }
+
+    #[test]
+    fn empty_preset() {
+        let registry = BlockRegistry::default();
+        let result = SuperflatGenerator::from_preset("", &registry);
+        assert!(matches!(result, Err(PresetError::InvalidLayer(_))));
+    }
+}
@@ -0,0 +1,118 @@
+use noise::{ NoiseFn, OpenSimplex, Perlin, Simplex };
+
+use super::config::{ NoiseType, TerrainConfig };
+use super::random;
+
+// A single octave of noise
+enum Source {
+    Perlin(Perlin),
+    Simplex(Simplex),
+    OpenSimplex(OpenSimplex),
+    Worley(u32),
+}
+
+impl Source {
+    fn new(noise_type: NoiseType, seed: u32) -> Self {
+        match noise_type {
+            NoiseType::Perlin => Source::Perlin(Perlin::new(seed)),
+            NoiseType::Simplex => Source::Simplex(Simplex::new(seed)),
+            NoiseType::OpenSimplex => Source::OpenSimplex(OpenSimplex::new(seed)),
+            NoiseType::Worley => Source::Worley(seed),
+        }
+    }
+
+    fn get(&self, point: [f64; 2]) -> f64 {
+        match self {
+            Source::Perlin(noise) => noise.get(point),
+            Source::Simplex(noise) => noise.get(point),
+            Source::OpenSimplex(noise) => noise.get(point),
+            Source::Worley(seed) => worley(*seed, point),
+        }
+    }
+
+    fn get3(&self, point: [f64; 3]) -> f64 {
+        match self {
+            Source::Perlin(noise) => noise.get(point),
+            Source::Simplex(noise) => noise.get(point),
+            Source::OpenSimplex(noise) => noise.get(point),
+            Source::Worley(seed) => worley(*seed, point),
+        }
+    }
+}
+
+// Cellular noise: the distance to the nearest of one randomly placed point per grid cell,
+// mapped to roughly [-1, 1]. The noise crate has its own Worley, but it can't be shared
+// with the generation threads.
+fn worley<const N: usize>(seed: u32, point: [f64; N]) -> f64 {
+    let cell = point.map(|value| value.floor() as i64);
+
+    let mut nearest = f64::MAX;
+    // Visit the cell containing the point and every cell around it, 3^N in total
+    for neighbor in 0..(3usize).pow(N as u32) {
+        let mut coords = [0; 3];
+        let mut offset = neighbor;
+        for axis in 0..N {
+            coords[axis] = cell[axis] + ((offset % 3) as i64) - 1;
+            offset /= 3;
+        }
+
+        let hash = random::hash3(seed, coords[0], coords[1], coords[2]);
+        let mut distance = 0.0;
+        for axis in 0..N {
+            let feature = (coords[axis] as f64) + random::unit_f64(hash.rotate_left((axis as u32) * 21));
+            distance += (feature - point[axis]).powi(2);
+        }
+        nearest = nearest.min(distance);
+    }
+
+    (nearest.sqrt() * 2.0 - 1.0).clamp(-1.0, 1.0)
+}
+
+// Sums several octaves of noise, each one at a higher frequency (lacunarity) and a
+// lower amplitude (persistence) than the last, so large hills get smaller details on them
+pub struct FractalNoise {
+    octaves: Vec<Source>,
+    frequency: f64,
+    lacunarity: f64,
+    persistence: f64,
+}
+
+impl FractalNoise {
+    // Uses the noise type and octave settings from the config, starting at the given frequency
+    pub fn new(seed: u32, config: &TerrainConfig, frequency: f64) -> Self {
+        Self {
+            octaves: (0..config.octaves.max(1))
+                .map(|octave| Source::new(config.noise_type, seed.wrapping_add(octave)))
+                .collect(),
+            frequency,
+            lacunarity: config.lacunarity,
+            persistence: config.persistence,
+        }
+    }
+
+    // Returns a value in roughly [-1, 1]
+    pub fn get(&self, x: f64, z: f64) -> f64 {
+        self.sum_octaves(|octave, frequency| octave.get([x * frequency, z * frequency]))
+    }
+
+    // Same as get, but sampled in 3D
+    pub fn get3(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.sum_octaves(|octave, frequency| octave.get3([x * frequency, y * frequency, z * frequency]))
+    }
+
+    fn sum_octaves(&self, sample: impl Fn(&Source, f64) -> f64) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut max_total = 0.0;
+
+        for octave in &self.octaves {
+            total += sample(octave, frequency) * amplitude;
+            max_total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+
+        total / max_total
+    }
+}
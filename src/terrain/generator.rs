@@ -1,62 +1,413 @@
-use noise::{ NoiseFn, Perlin };
+use super::biome::{ Biome, BiomeMap };
+use super::block::{ BlockId, BlockRegistry };
+use super::caves::{ CaveCarver, WormStep };
+use super::chunk::{ Chunk, CHUNK_HEIGHT, CHUNK_SIZE };
+use super::config::{ BiomeDensity, TerrainConfig, TerrainShape };
+use super::erosion::{ ErodedHeights, Erosion };
+use super::features::{ Boulder, Feature, FeatureWriter, Ruin, Tree };
+use super::fractal::FractalNoise;
+use super::ores::OrePlacer;
+use super::position::ChunkPos;
+use super::random::{ self, Rng };
 
+// Builds the chunks of a world. Generators run on the generation worker threads and must
+// give the same chunk for the same position every time.
+pub trait WorldGenerator: Send + Sync {
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk;
+}
+
+// The noise based generator with biomes, caves, ores and features
 pub struct TerrainGenerator {
-    noise: Perlin,
-    height_scale: f64,
-    noise_scale: f64,
+    noise: FractalNoise,
+    warp_x: FractalNoise,
+    warp_z: FractalNoise,
+    erosion: Erosion,
+    density_noise: FractalNoise,
+    caves: CaveCarver,
+    biomes: BiomeMap,
+    ores: OrePlacer,
+    features: Vec<(Box<dyn Feature>, BiomeDensity)>,
+    seed: u32,
+    config: TerrainConfig,
 }
 
 impl TerrainGenerator {
-    pub fn new(seed: u32) -> Self {
-        Self {
-            noise: Perlin::new(seed),
-            height_scale: 32.0, // Max height of terrain
-            noise_scale: 0.02, // Adjust this to change terrain frequency
-        }
+    // A generator with the built-in features, placed with the densities from the config
+    pub fn new(seed: u32, config: TerrainConfig, registry: &BlockRegistry) -> Self {
+        let densities = config.features.clone();
+        let mut generator = Self {
+            noise: FractalNoise::new(seed, &config, config.frequency),
+            warp_x: FractalNoise::new(seed.wrapping_add(0x6000), &config, config.warp_frequency),
+            warp_z: FractalNoise::new(seed.wrapping_add(0x7000), &config, config.warp_frequency),
+            erosion: Erosion::new(seed, &config.erosion),
+            density_noise: FractalNoise::new(seed.wrapping_add(0x1000), &config, config.density_frequency),
+            caves: CaveCarver::new(seed, &config),
+            biomes: BiomeMap::new(seed, &config),
+            ores: OrePlacer::new(seed, &config.ores, registry),
+            features: Vec::new(),
+            seed,
+            config,
+        };
+        generator.add_feature(Box::new(Tree), densities.trees);
+        generator.add_feature(Box::new(Boulder), densities.boulders);
+        generator.add_feature(Box::new(Ruin), densities.ruins);
+        generator
     }
 
-    pub fn get_height(&self, x: i32, z: i32) -> i32 {
-        let noise_value = self.noise.get([
-            (x as f64) * self.noise_scale,
-            (z as f64) * self.noise_scale,
-        ]);
+    // Adds a feature to place in every chunk, with its average number per chunk in each
+    // biome. Each feature gets its own random numbers based on the order it was added in,
+    // so adding one doesn't move the features added before it.
+    pub fn add_feature(&mut self, feature: Box<dyn Feature>, density: BiomeDensity) {
+        self.features.push((feature, density));
+    }
 
-        // Convert noise from [-1, 1] to [0, height_scale]
-        let height = ((noise_value + 1.0) * 0.5 * self.height_scale) as i32;
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        self.biomes.biome_at(x, z)
+    }
+
+    // Eroded heights are looked up through the given lookup, which should be kept around
+    // for nearby columns
+    pub fn get_height(&self, x: i32, z: i32, eroded: &mut ErodedHeights) -> i32 {
+        let height = if self.config.erosion.enabled {
+            eroded.get(x, z, |x, z| self.uneroded_height(x, z))
+        } else {
+            self.uneroded_height(x, z)
+        };
 
         // Ensure minimum height of 1 block
-        height.max(1)
+        (height as i32).max(1)
+    }
+
+    fn uneroded_height(&self, x: i32, z: i32) -> f64 {
+        // Domain warping: sample the noise somewhere else, offset by more noise
+        let mut sample_x = x as f64;
+        let mut sample_z = z as f64;
+        if self.config.warp_strength != 0.0 {
+            sample_x += self.warp_x.get(x as f64, z as f64) * self.config.warp_strength;
+            sample_z += self.warp_z.get(x as f64, z as f64) * self.config.warp_strength;
+        }
+        let noise_value = self.noise.get(sample_x, sample_z);
+
+        // Every biome shapes the noise with its own height curve, mixed by how much each
+        // biome contributes here so there are no cliffs at biome borders
+        let weights = self.biomes.weights(x, z);
+        let offset: f64 = Biome::ALL
+            .iter()
+            .zip(weights)
+            .map(|(biome, weight)| biome.height(noise_value) * weight)
+            .sum();
+        self.config.base_height + offset * self.config.amplitude
+    }
+
+    // Positive inside the ground. The height gradient is zero at the heightmap surface and
+    // changes by one every amplitude blocks, the 3D noise then moves the surface up or down
+    // locally, which carves out overhangs and lifts off floating islands.
+    fn density(&self, x: i32, y: i32, z: i32, height: i32) -> f64 {
+        let gradient = ((height - y) as f64) / self.config.amplitude.max(1.0);
+        let noise_value = self.density_noise.get3(x as f64, y as f64, z as f64);
+        gradient + noise_value * self.config.overhang_strength
+    }
+
+    // Highest block the shaping stage can fill in a column with the given height. The
+    // density noise can't raise the ground further than this above the heightmap.
+    fn column_top(&self, height: i32) -> i32 {
+        match self.config.shape {
+            TerrainShape::Heightmap => height,
+            TerrainShape::Density => {
+                let top = (height as f64) + self.config.overhang_strength.abs() * self.config.amplitude;
+                (top.ceil() as i32).min(CHUNK_HEIGHT - 1)
+            }
+        }
+    }
+
+    // Whether the shaping stage fills a block, before caves are carved out of it
+    fn is_ground(&self, x: i32, y: i32, z: i32, height: i32) -> bool {
+        match self.config.shape {
+            TerrainShape::Heightmap => y <= height,
+            TerrainShape::Density => {
+                y == 0 || (y <= self.column_top(height) && self.density(x, y, z, height) > 0.0)
+            }
+        }
+    }
+
+    // The top block of a column once the ground is shaped and the caves are carved, worked
+    // out without generating the column's chunk. This gives the same answer as looking at
+    // the generated chunk, which matters for features that reach across chunk borders.
+    fn surface_at(&self, x: i32, z: i32, worms: &[WormStep], eroded: &mut ErodedHeights) -> i32 {
+        let height = self.get_height(x, z, eroded).min(CHUNK_HEIGHT - 1);
+        (0..=self.column_top(height))
+            .rev()
+            .find(|&y| self.is_ground(x, y, z, height) && !self.caves.is_carved([x, y, z], worms))
+            .unwrap_or(0)
+    }
+
+    // Stone turns into deepslate over a few blocks around the deepslate level, with the two
+    // mixed in between so there's no flat line where they meet
+    fn stone_at(&self, x: i32, y: i32, z: i32) -> BlockId {
+        const TRANSITION: i32 = 4;
+        let depth = self.config.deepslate_level + TRANSITION / 2 - y;
+        if depth <= 0 {
+            return BlockId::STONE;
+        }
+        let hash = random::hash3(self.seed, x as i64, y as i64, z as i64);
+        if random::unit_f64(hash) < (depth as f64) / (TRANSITION as f64) {
+            BlockId::DEEPSLATE
+        } else {
+            BlockId::STONE
+        }
+    }
+
+    // Picks the block for a voxel in a column whose surface is at surface_height. There are
+    // a few blocks of the biome's filler under the surface, and stone below that.
+    fn block_at(&self, [x, y, z]: [i32; 3], surface_height: i32, biome: Biome) -> BlockId {
+        let snow_above = self.config.base_height + self.config.amplitude * 1.5;
+        let beach_below = self.config.sea_level + self.config.beach_height;
+
+        if surface_height <= beach_below {
+            // Beaches and the sea floor are sand whatever the biome
+            if y > surface_height - 4 { BlockId::SAND } else { self.stone_at(x, y, z) }
+        } else if y == surface_height {
+            // High peaks are covered in snow whatever the biome
+            if (surface_height as f64) > snow_above { BlockId::SNOW } else { biome.surface_block() }
+        } else if y > surface_height - 4 {
+            biome.filler_block()
+        } else {
+            self.stone_at(x, y, z)
+        }
+    }
+
+    // Places every feature that can reach into the chunk. Where features go is worked out
+    // from the seed and the biome function, and their ground comes out the same whether
+    // it's read off this chunk or worked out by surface_at, so each chunk sees the same
+    // features whatever order chunks are generated in.
+    fn place_features(&self, chunk: &mut Chunk) {
+        let origin = chunk.pos.origin();
+
+        // The ground of the chunk's own columns is read off the chunk before any feature
+        // goes in, so features don't end up stacked on top of each other
+        let mut ground = vec![0; (CHUNK_SIZE * CHUNK_SIZE) as usize];
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                ground[(x + z * CHUNK_SIZE) as usize] = (0..CHUNK_HEIGHT)
+                    .rev()
+                    .find(|&y| !chunk.get(x, y, z).is_air())
+                    .unwrap_or(0);
+            }
+        }
+
+        // Features starting in other chunks need the caves to find their ground
+        let max_range = self.features
+            .iter()
+            .map(|(feature, _)| (feature.radius() + CHUNK_SIZE - 1) / CHUNK_SIZE)
+            .max()
+            .unwrap_or(0);
+        let worms = self.caves.worms_near(chunk.pos, max_range * CHUNK_SIZE);
+        let mut eroded = self.erosion.heights();
+
+        for (index, (feature, density)) in self.features.iter().enumerate() {
+            let max_density = density.max();
+            if max_density <= 0.0 {
+                continue;
+            }
+
+            let range = (feature.radius() + CHUNK_SIZE - 1) / CHUNK_SIZE;
+            for origin_x in chunk.pos.x - range..=chunk.pos.x + range {
+                for origin_z in chunk.pos.z - range..=chunk.pos.z + range {
+                    let seed = self.seed.wrapping_add(0x5000 + (index as u32));
+                    let mut rng = Rng::new(random::hash3(seed, origin_x as i64, 0, origin_z as i64));
+
+                    // Candidates are spread at the highest density of any biome, then thinned
+                    // out to the density of the biome each one lands in
+                    for _ in 0..rng.count(max_density) {
+                        let x = origin_x * CHUNK_SIZE + rng.range_i32(0, CHUNK_SIZE - 1);
+                        let z = origin_z * CHUNK_SIZE + rng.range_i32(0, CHUNK_SIZE - 1);
+                        let keep = rng.next_f64() * max_density < density.get(self.biome_at(x, z));
+                        // Every candidate gets its own stream, so skipping one doesn't shift the
+                        // random numbers of the others
+                        let mut feature_rng = Rng::new(rng.next_u64());
+
+                        if !keep {
+                            continue;
+                        }
+                        let (local_x, local_z) = (x - origin.x, z - origin.z);
+                        let ground = if Chunk::in_bounds(local_x, 0, local_z) {
+                            ground[(local_x + local_z * CHUNK_SIZE) as usize]
+                        } else {
+                            self.surface_at(x, z, &worms, &mut eroded)
+                        };
+                        // Columns topped by water don't get features either
+                        if ground <= self.config.sea_level {
+                            continue;
+                        }
+                        let mut writer = FeatureWriter::new(chunk, [x, ground + 1, z]);
+                        feature.place(&mut writer, &mut feature_rng);
+                    }
+                }
+            }
+        }
+    }
+
+    // Fills open air up to sea level with water. Each column is filled from sea level down
+    // to the first block of ground, so caves below the sea floor stay dry.
+    fn fill_water(&self, chunk: &mut Chunk) {
+        let sea_level = self.config.sea_level.min(CHUNK_HEIGHT - 1);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in (0..=sea_level).rev() {
+                    if !chunk.get(x, y, z).is_air() {
+                        break;
+                    }
+                    chunk.set(x, y, z, BlockId::WATER);
+                }
+            }
+        }
     }
 
-    pub fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Vec<bool> {
-        let chunk_size = 16;
-        let max_height = 256;
-        let mut voxels = vec![false; (chunk_size * chunk_size * max_height) as usize];
+    // Fills a column from top down to the bottom of the world wherever is_solid says so.
+    // Every solid run gets its own surface, so the top of an overhang is grass like the
+    // ground below it.
+    fn fill_column(
+        &self,
+        chunk: &mut Chunk,
+        x: i32,
+        z: i32,
+        top: i32,
+        biome: Biome,
+        is_solid: impl Fn(i32) -> bool
+    ) {
+        let origin = chunk.pos.origin();
+        let world_x = origin.x + x;
+        let world_z = origin.z + z;
+        let mut surface = None;
+        for y in (0..=top).rev() {
+            if !is_solid(y) {
+                surface = None;
+                continue;
+            }
+            let surface_height = *surface.get_or_insert(y);
+            chunk.set(x, y, z, self.block_at([world_x, y, world_z], surface_height, biome));
+        }
+    }
+}
 
-        let world_x_base = chunk_x * chunk_size;
-        let world_z_base = chunk_z * chunk_size;
+impl WorldGenerator for TerrainGenerator {
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        let origin = pos.origin();
 
         // Generate heights first
-        let mut heights = vec![0; (chunk_size * chunk_size) as usize];
-        for x in 0..chunk_size {
-            for z in 0..chunk_size {
+        let mut heights = vec![0; (CHUNK_SIZE * CHUNK_SIZE) as usize];
+        let mut eroded = self.erosion.heights();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
                 let height = self
-                    .get_height(world_x_base + x, world_z_base + z)
-                    .min(max_height - 1);
-                heights[(x as usize) + (z as usize) * (chunk_size as usize)] = height;
+                    .get_height(origin.x + x, origin.z + z, &mut eroded)
+                    .min(CHUNK_HEIGHT - 1);
+                heights[(x as usize) + (z as usize) * (CHUNK_SIZE as usize)] = height;
+            }
+        }
+
+        // Fill voxels based on pre-calculated heights. Only the ground is written, so the
+        // sections above the terrain stay empty and are never allocated.
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let height = heights[(x as usize) + (z as usize) * (CHUNK_SIZE as usize)];
+                let world_x = origin.x + x;
+                let world_z = origin.z + z;
+                let biome = self.biome_at(world_x, world_z);
+                chunk.set_biome(x, z, biome);
+
+                let top = self.column_top(height);
+                self.fill_column(&mut chunk, x, z, top, biome, |y| {
+                    self.is_ground(world_x, y, world_z, height)
+                });
+            }
+        }
+
+        // Ores go in before the caves, so caves cut through veins and show them off
+        self.ores.place(&mut chunk);
+        self.caves.carve(&mut chunk);
+        self.fill_water(&mut chunk);
+        self.place_features(&mut chunk);
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::config::FeatureConfig;
+
+    fn assert_same_blocks(actual: &Chunk, expected: &Chunk) {
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_HEIGHT {
+                    assert_eq!(
+                        actual.get(x, y, z),
+                        expected.get(x, y, z),
+                        "block {} {} {} of chunk {:?}",
+                        x,
+                        y,
+                        z,
+                        actual.pos
+                    );
+                }
             }
         }
+    }
+
+    #[test]
+    fn chunk_does_not_depend_on_generation_order() {
+        let registry = BlockRegistry::default();
+        let pos = ChunkPos::new(2, -3);
+        let alone = TerrainGenerator::new(7, TerrainConfig::default(), &registry)
+            .generate_chunk(pos);
+
+        let generator = TerrainGenerator::new(7, TerrainConfig::default(), &registry);
+        for neighbor in pos.neighbors() {
+            generator.generate_chunk(neighbor);
+        }
+        let after_neighbors = generator.generate_chunk(pos);
+
+        assert_same_blocks(&after_neighbors, &alone);
+    }
+
+    #[test]
+    fn surface_matches_generated_chunk() {
+        let registry = BlockRegistry::default();
+        let config = TerrainConfig {
+            shape: TerrainShape::Density,
+            // Features would sit on top of the ground
+            features: FeatureConfig {
+                trees: BiomeDensity::default(),
+                boulders: BiomeDensity::default(),
+                ruins: BiomeDensity::default(),
+            },
+            ..TerrainConfig::default()
+        };
+        assert!(config.caves.enabled);
+        let generator = TerrainGenerator::new(7, config, &registry);
 
-        // Fill voxels based on pre-calculated heights
-        for x in 0..chunk_size {
-            for z in 0..chunk_size {
-                let height = heights[(x as usize) + (z as usize) * (chunk_size as usize)];
-                for y in 0..=height {
-                    let index = x + z * chunk_size + y * chunk_size * chunk_size;
-                    voxels[index as usize] = true;
+        let mut eroded = generator.erosion.heights();
+        for pos in [ChunkPos::new(0, 0), ChunkPos::new(-1, 2), ChunkPos::new(3, -2)] {
+            let chunk = generator.generate_chunk(pos);
+            let worms = generator.caves.worms_near(pos, 0);
+            let origin = pos.origin();
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let top = (0..CHUNK_HEIGHT)
+                        .rev()
+                        .find(|&y| !chunk.get(x, y, z).is_air())
+                        .unwrap_or(0);
+                    // Lower columns are topped by water
+                    if top <= generator.config.sea_level {
+                        continue;
+                    }
+                    let (world_x, world_z) = (origin.x + x, origin.z + z);
+                    let surface = generator.surface_at(world_x, world_z, &worms, &mut eroded);
+                    assert_eq!(surface, top, "column {} {} of chunk {:?}", x, z, pos);
                 }
             }
         }
-        voxels
     }
 }
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use super::block::{ BlockId, BlockRegistry };
+use super::chunk::{ Chunk, CHUNK_HEIGHT, CHUNK_SIZE };
+use super::generator::WorldGenerator;
+use super::position::ChunkPos;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    Png(png::DecodingError),
+    Pgm(String),
+    UnknownFormat,
+    Empty,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(error) => write!(f, "could not read image: {}", error),
+            ImageError::Png(error) => write!(f, "invalid PNG: {}", error),
+            ImageError::Pgm(message) => write!(f, "invalid PGM: {}", message),
+            ImageError::UnknownFormat => write!(f, "not a PNG or PGM image"),
+            ImageError::Empty => write!(f, "the image has no pixels"),
+        }
+    }
+}
+
+// An image with every pixel as an RGB color in [0, 1]. Grayscale images have the same
+// value in all three channels.
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f64; 3]>,
+}
+
+impl Image {
+    fn load(path: &Path) -> Result<Self, ImageError> {
+        let bytes = std::fs::read(path).map_err(ImageError::Io)?;
+        let image = if bytes.starts_with(b"\x89PNG") {
+            Self::load_png(path)?
+        } else if bytes.starts_with(b"P2") || bytes.starts_with(b"P5") {
+            Self::parse_pgm(&bytes)?
+        } else {
+            return Err(ImageError::UnknownFormat);
+        };
+
+        if image.width == 0 || image.height == 0 {
+            return Err(ImageError::Empty);
+        }
+        Ok(image)
+    }
+
+    fn load_png(path: &Path) -> Result<Self, ImageError> {
+        let file = File::open(path).map_err(ImageError::Io)?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        // Expands palettes to RGB and low bit depths to 8 bits, 16 bit images stay 16 bit
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(ImageError::Png)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(ImageError::Png)?;
+
+        let channels = info.color_type.samples();
+        let sample = |index: usize| -> f64 {
+            match info.bit_depth {
+                png::BitDepth::Sixteen => {
+                    (u16::from_be_bytes([buffer[index * 2], buffer[index * 2 + 1]]) as f64) / 65535.0
+                }
+                _ => (buffer[index] as f64) / 255.0,
+            }
+        };
+
+        let mut pixels = Vec::with_capacity((info.width * info.height) as usize);
+        for pixel in 0..(info.width * info.height) as usize {
+            let first = pixel * channels;
+            // Grayscale has one channel and RGB three, alpha comes last and is ignored
+            pixels.push(if channels < 3 {
+                [sample(first); 3]
+            } else {
+                [sample(first), sample(first + 1), sample(first + 2)]
+            });
+        }
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    // Reads the plain (P2) and binary (P5) variants of the PGM format
+    fn parse_pgm(bytes: &[u8]) -> Result<Self, ImageError> {
+        let error = |message: &str| ImageError::Pgm(message.to_string());
+
+        // The header is whitespace separated values, with comments running from # to the
+        // end of the line. In binary files the pixels start right after one whitespace
+        // character following the max value.
+        let mut position = 0;
+        let mut next_value = || -> Option<u32> {
+            loop {
+                match bytes.get(position)? {
+                    b'#' => {
+                        while bytes.get(position).is_some_and(|&byte| byte != b'\n') {
+                            position += 1;
+                        }
+                    }
+                    byte if byte.is_ascii_whitespace() => {
+                        position += 1;
+                    }
+                    _ => {
+                        break;
+                    }
+                }
+            }
+            let start = position;
+            while bytes.get(position).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+                position += 1;
+            }
+            std::str::from_utf8(&bytes[start..position]).ok()?.parse().ok()
+        };
+
+        let _magic = next_value();
+        let width = next_value().ok_or_else(|| error("missing width"))?;
+        let height = next_value().ok_or_else(|| error("missing height"))?;
+        let max_value = next_value().ok_or_else(|| error("missing max value"))?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(error("max value out of range"));
+        }
+
+        let count = (width as usize) * (height as usize);
+        let mut values = Vec::new();
+        if bytes.starts_with(b"P2") {
+            for _ in 0..count {
+                values.push(next_value().ok_or_else(|| error("not enough pixels"))?);
+            }
+        } else {
+            let data = bytes.get(position + 1..).ok_or_else(|| error("not enough pixels"))?;
+            let sample_size = if max_value > 255 { 2 } else { 1 };
+            if count.checked_mul(sample_size).is_none_or(|size| data.len() < size) {
+                return Err(error("not enough pixels"));
+            }
+            for index in 0..count {
+                values.push(match sample_size {
+                    2 => u16::from_be_bytes([data[index * 2], data[index * 2 + 1]]) as u32,
+                    _ => data[index] as u32,
+                });
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels: values
+                .into_iter()
+                .map(|value| [(value.min(max_value) as f64) / (max_value as f64); 3])
+                .collect(),
+        })
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> [f64; 3] {
+        self.pixels[(x + y * self.width) as usize]
+    }
+}
+
+// What happens past the edges of the image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EdgeMode {
+    // The image repeats forever
+    Tile,
+    // The outermost pixels stretch out forever
+    Clamp,
+}
+
+pub struct HeightmapOptions {
+    // Height in blocks of a white pixel above a black one
+    pub scale: f64,
+    // Height in blocks of a black pixel
+    pub offset: f64,
+    pub edges: EdgeMode,
+}
+
+impl Default for HeightmapOptions {
+    fn default() -> Self {
+        Self {
+            scale: 64.0,
+            offset: 1.0,
+            edges: EdgeMode::Clamp,
+        }
+    }
+}
+
+// Builds terrain from a grayscale heightmap image, one pixel per column with pixel (0, 0)
+// at the world origin. An optional color map of the same layout picks the surface block of
+// each column, using the block whose color is closest to the pixel's.
+pub struct HeightmapGenerator {
+    heights: Image,
+    colors: Option<Image>,
+    // Blocks the color map can pick from, with their colors
+    surface_blocks: Vec<(BlockId, [f64; 3])>,
+    options: HeightmapOptions,
+}
+
+impl HeightmapGenerator {
+    pub fn load(
+        heightmap: &Path,
+        color_map: Option<&Path>,
+        options: HeightmapOptions,
+        registry: &BlockRegistry
+    ) -> Result<Self, ImageError> {
+        let heights = Image::load(heightmap)?;
+        let colors = color_map.map(Image::load).transpose()?;
+
+        let surface_blocks = registry
+            .blocks()
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (BlockId(index as u16), block))
+            .filter(|(id, _)| registry.is_opaque(*id))
+            .map(|(id, block)| (id, block.color.map(|channel| channel as f64)))
+            .collect();
+
+        Ok(Self {
+            heights,
+            colors,
+            surface_blocks,
+            options,
+        })
+    }
+
+    // Maps a world position onto a pixel of the image, following the edge mode
+    fn pixel_position(&self, image: &Image, x: i32, z: i32) -> (u32, u32) {
+        let (width, height) = (image.width as i32, image.height as i32);
+        match self.options.edges {
+            EdgeMode::Tile => (x.rem_euclid(width) as u32, z.rem_euclid(height) as u32),
+            EdgeMode::Clamp => (x.clamp(0, width - 1) as u32, z.clamp(0, height - 1) as u32),
+        }
+    }
+
+    fn height_at(&self, x: i32, z: i32) -> i32 {
+        let (pixel_x, pixel_z) = self.pixel_position(&self.heights, x, z);
+        // Luminance, for heightmaps that were saved in color
+        let [r, g, b] = self.heights.pixel(pixel_x, pixel_z);
+        let value = r * 0.299 + g * 0.587 + b * 0.114;
+
+        let height = self.options.offset + value * self.options.scale;
+        (height.round() as i32).clamp(0, CHUNK_HEIGHT - 1)
+    }
+
+    fn surface_at(&self, x: i32, z: i32) -> BlockId {
+        let Some(colors) = &self.colors else {
+            return BlockId::GRASS;
+        };
+        let (pixel_x, pixel_z) = self.pixel_position(colors, x, z);
+        let color = colors.pixel(pixel_x, pixel_z);
+
+        let distance = |block_color: &[f64; 3]| -> f64 {
+            (0..3).map(|channel| (block_color[channel] - color[channel]).powi(2)).sum()
+        };
+        self.surface_blocks
+            .iter()
+            .min_by(|a, b| distance(&a.1).total_cmp(&distance(&b.1)))
+            .map_or(BlockId::GRASS, |(block, _)| *block)
+    }
+}
+
+impl WorldGenerator for HeightmapGenerator {
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        let origin = pos.origin();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let world_x = origin.x + x;
+                let world_z = origin.z + z;
+                let height = self.height_at(world_x, world_z);
+                let surface = self.surface_at(world_x, world_z);
+                // Grass sits on dirt, other surface blocks go a few blocks deep
+                let filler = if surface == BlockId::GRASS { BlockId::DIRT } else { surface };
+
+                for y in 0..=height {
+                    let block = if y == height {
+                        surface
+                    } else if y > height - 4 {
+                        filler
+                    } else {
+                        BlockId::STONE
+                    };
+                    chunk.set(x, y, z, block);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(image: &Image) -> Vec<f64> {
+        image.pixels.iter().map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn truncated_binary_body() {
+        let result = Image::parse_pgm(b"P5 3 2 255\n\x00\x10\x20\x30");
+        assert!(matches!(result, Err(ImageError::Pgm(_))));
+        // Nothing at all after the max value
+        assert!(matches!(Image::parse_pgm(b"P5 3 2 255"), Err(ImageError::Pgm(_))));
+        // A size so large the byte count overflows
+        let result = Image::parse_pgm(b"P5 4294967295 4294967295 65535\n\x00\x00");
+        assert!(matches!(result, Err(ImageError::Pgm(_))));
+    }
+
+    #[test]
+    fn sixteen_bit_binary() {
+        let image = Image::parse_pgm(b"P5 2 1 1000\n\x01\xf4\x03\xe8").unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(gray(&image), vec![0.5, 1.0]);
+    }
+
+    #[test]
+    fn plain_with_comments() {
+        let bytes = b"P2\n# made by hand\n2 2 # width and height\n4\n0 1 # first row\n2\n#\n4\n";
+        let image = Image::parse_pgm(bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(gray(&image), vec![0.0, 0.25, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn zero_max_value() {
+        assert!(matches!(Image::parse_pgm(b"P2 1 1 0\n0\n"), Err(ImageError::Pgm(_))));
+        assert!(matches!(Image::parse_pgm(b"P5 1 1 0\n\x00"), Err(ImageError::Pgm(_))));
+    }
+}
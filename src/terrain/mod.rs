@@ -1,2 +1,17 @@
 pub mod generator;
 pub mod chunk;
+pub mod block;
+pub mod config;
+pub mod biome;
+pub mod position;
+pub mod raycast;
+pub mod world;
+pub mod flat;
+pub mod heightmap;
+pub mod features;
+pub mod random;
+mod caves;
+mod erosion;
+mod fractal;
+mod ores;
+mod palette;
@@ -0,0 +1,73 @@
+use super::block::{ BlockId, BlockRegistry };
+use super::chunk::{ Chunk, CHUNK_SIZE };
+use super::config::OreConfig;
+use super::random::{ self, Rng };
+
+// A vein is a short random walk of ore through stone
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+// Scatters ore veins through the stone of a chunk. Every chunk and ore gets its own random
+// stream from the seed, so a chunk always gets the same ores no matter when it's generated.
+// Veins are kept inside the chunk they start in.
+pub struct OrePlacer {
+    seed: u32,
+    ores: Vec<(BlockId, OreConfig)>,
+}
+
+impl OrePlacer {
+    // Ores whose block isn't in the registry are left out with a warning
+    pub fn new(seed: u32, ores: &[OreConfig], registry: &BlockRegistry) -> Self {
+        let ores = ores
+            .iter()
+            .filter_map(|ore| {
+                let block = registry.find(&ore.block);
+                if block.is_none() {
+                    eprintln!("Unknown ore block {}, skipping it", ore.block);
+                }
+                block.map(|block| (block, ore.clone()))
+            })
+            .collect();
+
+        Self { seed, ores }
+    }
+
+    pub fn place(&self, chunk: &mut Chunk) {
+        for (index, (block, ore)) in self.ores.iter().enumerate() {
+            let mut rng = Rng::new(
+                random::hash3(self.seed, chunk.pos.x as i64, index as i64, chunk.pos.z as i64)
+            );
+
+            for _ in 0..rng.count(ore.veins_per_chunk) {
+                let mut position = [
+                    rng.range_i32(0, CHUNK_SIZE - 1),
+                    rng.range_i32(ore.min_height, ore.max_height),
+                    rng.range_i32(0, CHUNK_SIZE - 1),
+                ];
+
+                for _ in 0..ore.vein_size {
+                    let [x, y, z] = position;
+                    // Ore only replaces stone, so it never shows up in the dirt or in the air
+                    if Self::is_stone(chunk.get(x, y, z)) {
+                        chunk.set(x, y, z, *block);
+                    }
+
+                    let step = DIRECTIONS[rng.range_i32(0, 5) as usize];
+                    for (value, offset) in position.iter_mut().zip(step) {
+                        *value += offset;
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_stone(block: BlockId) -> bool {
+        block == BlockId::STONE || block == BlockId::DEEPSLATE
+    }
+}
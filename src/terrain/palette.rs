@@ -0,0 +1,251 @@
+use super::block::BlockId;
+
+// Stores a fixed number of block ids as bit-packed indices into a small palette of the
+// distinct blocks that are actually used. A chunk that only holds air, stone and dirt
+// needs 2 bits per block instead of 16, and a uniform chunk needs no per-block data at all.
+#[derive(Clone)]
+pub struct PalettedStorage {
+    palette: Vec<BlockId>,
+    // Number of entries pointing at each palette slot, slots with a count of 0 are free
+    counts: Vec<u32>,
+    bits: u32,
+    data: Vec<u64>,
+    len: usize,
+}
+
+impl PalettedStorage {
+    pub fn new(len: usize, fill: BlockId) -> Self {
+        Self {
+            palette: vec![fill],
+            counts: vec![len as u32],
+            bits: 0,
+            data: Vec::new(),
+            len,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> BlockId {
+        self.palette[self.read(index)]
+    }
+
+    pub fn set(&mut self, index: usize, block: BlockId) {
+        let old_slot = self.read(index);
+        if self.palette[old_slot] == block {
+            return;
+        }
+
+        let new_slot = self.slot_for(block);
+        self.write(index, new_slot);
+        self.counts[new_slot] += 1;
+        self.counts[old_slot] -= 1;
+
+        if self.counts[old_slot] == 0 {
+            self.shrink();
+        }
+    }
+
+    // The distinct blocks currently stored
+    pub fn blocks(&self) -> impl Iterator<Item = BlockId> + '_ {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, &count)| count > 0)
+            .map(|(&block, _)| block)
+    }
+
+    // Approximate heap and inline size of this storage in bytes
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>() +
+            self.palette.capacity() * std::mem::size_of::<BlockId>() +
+            self.counts.capacity() * std::mem::size_of::<u32>() +
+            self.data.capacity() * std::mem::size_of::<u64>()
+    }
+
+    // Finds the palette slot for a block, adding it to the palette if needed
+    fn slot_for(&mut self, block: BlockId) -> usize {
+        if let Some(slot) = self.palette.iter().position(|&b| b == block) {
+            return slot;
+        }
+
+        if let Some(slot) = self.counts.iter().position(|&count| count == 0) {
+            self.palette[slot] = block;
+            return slot;
+        }
+
+        self.palette.push(block);
+        self.counts.push(0);
+
+        let needed_bits = Self::bits_for(self.palette.len());
+        if needed_bits > self.bits {
+            self.repack(needed_bits, |slot| slot);
+        }
+        self.palette.len() - 1
+    }
+
+    // Drops unused palette slots once the remaining ones fit into a smaller bit width
+    fn shrink(&mut self) {
+        let used = self.counts.iter().filter(|&&count| count > 0).count();
+        let needed_bits = Self::bits_for(used);
+        if needed_bits >= self.bits {
+            return;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(used);
+        let mut counts = Vec::with_capacity(used);
+        for (slot, (&block, &count)) in self.palette.iter().zip(&self.counts).enumerate() {
+            if count > 0 {
+                remap[slot] = palette.len();
+                palette.push(block);
+                counts.push(count);
+            }
+        }
+
+        self.repack(needed_bits, |slot| remap[slot]);
+        self.palette = palette;
+        self.counts = counts;
+    }
+
+    // Rewrites every entry with a new bit width, mapping old palette slots to new ones
+    fn repack(&mut self, bits: u32, remap: impl Fn(usize) -> usize) {
+        let slots: Vec<usize> = (0..self.len).map(|index| remap(self.read(index))).collect();
+
+        self.bits = bits;
+        self.data = if bits == 0 {
+            Vec::new()
+        } else {
+            vec![0; self.len.div_ceil(Self::entries_per_word(bits))]
+        };
+
+        if bits > 0 {
+            for (index, slot) in slots.into_iter().enumerate() {
+                self.write(index, slot);
+            }
+        }
+    }
+
+    fn read(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = Self::entries_per_word(self.bits);
+        let shift = ((index % per_word) as u32) * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.data[index / per_word] >> shift) & mask) as usize
+    }
+
+    fn write(&mut self, index: usize, slot: usize) {
+        let per_word = Self::entries_per_word(self.bits);
+        let shift = ((index % per_word) as u32) * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        let word = &mut self.data[index / per_word];
+        *word = (*word & !mask) | (((slot as u64) << shift) & mask);
+    }
+
+    // Entries never straddle two words, so some high bits of each word may go unused
+    fn entries_per_word(bits: u32) -> usize {
+        (64 / bits) as usize
+    }
+
+    fn bits_for(palette_len: usize) -> u32 {
+        if palette_len <= 1 { 0 } else { usize::BITS - (palette_len - 1).leading_zeros() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::random::Rng;
+
+    const LEN: usize = 4096;
+
+    #[test]
+    fn grows_bit_width() {
+        let mut storage = PalettedStorage::new(LEN, BlockId::AIR);
+        assert_eq!(storage.bits, 0);
+        assert!(storage.data.is_empty());
+
+        storage.set(10, BlockId::STONE);
+        assert_eq!(storage.bits, 1);
+        storage.set(20, BlockId::DIRT);
+        assert_eq!(storage.bits, 2);
+        storage.set(30, BlockId::GRASS);
+        assert_eq!(storage.bits, 2);
+        storage.set(40, BlockId::SAND);
+        assert_eq!(storage.bits, 3);
+
+        assert_eq!(storage.get(0), BlockId::AIR);
+        assert_eq!(storage.get(10), BlockId::STONE);
+        assert_eq!(storage.get(20), BlockId::DIRT);
+        assert_eq!(storage.get(30), BlockId::GRASS);
+        assert_eq!(storage.get(40), BlockId::SAND);
+    }
+
+    #[test]
+    fn shrinks_back_to_zero_bits() {
+        let mut storage = PalettedStorage::new(LEN, BlockId::AIR);
+        let blocks = [BlockId::STONE, BlockId::DIRT, BlockId::GRASS, BlockId::SAND];
+        for (index, &block) in blocks.iter().enumerate() {
+            storage.set(index, block);
+        }
+        assert_eq!(storage.bits, 3);
+
+        for index in 0..blocks.len() {
+            storage.set(index, BlockId::AIR);
+        }
+        assert_eq!(storage.bits, 0);
+        assert!(storage.data.is_empty());
+        assert_eq!(storage.blocks().collect::<Vec<_>>(), vec![BlockId::AIR]);
+        assert!((0..LEN).all(|index| storage.get(index) == BlockId::AIR));
+    }
+
+    #[test]
+    fn reuses_freed_slot() {
+        let mut storage = PalettedStorage::new(LEN, BlockId::AIR);
+        storage.set(1, BlockId::STONE);
+        storage.set(2, BlockId::DIRT);
+        storage.set(3, BlockId::SAND);
+        assert_eq!(storage.bits, 2);
+
+        // Three blocks are left, which still need 2 bits, so the slot is only freed
+        storage.set(3, BlockId::AIR);
+        assert_eq!(storage.bits, 2);
+        assert_eq!(storage.palette.len(), 4);
+
+        let data = storage.data.as_ptr();
+        storage.set(4, BlockId::SNOW);
+        assert_eq!(storage.bits, 2);
+        let palette = vec![BlockId::AIR, BlockId::STONE, BlockId::DIRT, BlockId::SNOW];
+        assert_eq!(storage.palette, palette);
+        assert_eq!(storage.data.as_ptr(), data);
+        assert_eq!(storage.get(3), BlockId::AIR);
+        assert_eq!(storage.get(4), BlockId::SNOW);
+    }
+
+    #[test]
+    fn matches_plain_vec() {
+        let mut storage = PalettedStorage::new(LEN, BlockId::AIR);
+        let mut model = vec![BlockId::AIR; LEN];
+        let mut rng = Rng::new(1);
+
+        // Each round uses a different number of distinct blocks, so the bit width goes up
+        // and down between rounds
+        for distinct in [2, 40, 5, 1, 17, 3, 1] {
+            for _ in 0..LEN * 2 {
+                let index = rng.range_i32(0, (LEN as i32) - 1) as usize;
+                let block = BlockId(rng.range_i32(0, distinct - 1) as u16);
+                storage.set(index, block);
+                model[index] = block;
+            }
+            for (index, &block) in model.iter().enumerate() {
+                assert_eq!(storage.get(index), block, "index {}", index);
+            }
+            let mut blocks: Vec<BlockId> = storage.blocks().collect();
+            blocks.sort_by_key(|block| block.0);
+            let mut expected = model.clone();
+            expected.sort_by_key(|block| block.0);
+            expected.dedup();
+            assert_eq!(blocks, expected);
+        }
+    }
+}
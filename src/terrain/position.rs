@@ -0,0 +1,108 @@
+use cgmath::Point3;
+use std::ops::Add;
+
+use super::chunk::CHUNK_SIZE;
+
+// The position of a block in the world. The block fills the unit cube from its position
+// to its position plus one on every axis.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WorldPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+// The position of a chunk on the chunk grid, chunk (1, 0) starts at world x = CHUNK_SIZE
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+// The position of a block inside its chunk, with x and z in 0..CHUNK_SIZE
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LocalPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl WorldPos {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    // The block containing a point
+    pub fn containing(point: Point3<f32>) -> Self {
+        Self::new(point.x.floor() as i32, point.y.floor() as i32, point.z.floor() as i32)
+    }
+
+    // Euclidean division keeps negative positions in the right chunk, -1 is the last
+    // block of chunk -1 rather than in chunk 0
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos::new(self.x.div_euclid(CHUNK_SIZE), self.z.div_euclid(CHUNK_SIZE))
+    }
+
+    pub fn local(self) -> LocalPos {
+        LocalPos {
+            x: self.x.rem_euclid(CHUNK_SIZE),
+            y: self.y,
+            z: self.z.rem_euclid(CHUNK_SIZE),
+        }
+    }
+}
+
+impl ChunkPos {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    pub fn offset(self, dx: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.z + dz)
+    }
+
+    // The chunks sharing a side with this one
+    pub fn neighbors(self) -> [ChunkPos; 4] {
+        [self.offset(1, 0), self.offset(-1, 0), self.offset(0, 1), self.offset(0, -1)]
+    }
+
+    // The world position of the chunk's block (0, 0, 0)
+    pub fn origin(self) -> WorldPos {
+        WorldPos::new(self.x * CHUNK_SIZE, 0, self.z * CHUNK_SIZE)
+    }
+}
+
+// A chunk's origin plus a position inside the chunk gives the block's world position
+impl Add<LocalPos> for WorldPos {
+    type Output = WorldPos;
+
+    fn add(self, local: LocalPos) -> WorldPos {
+        WorldPos::new(self.x + local.x, self.y + local.y, self.z + local.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_positions() {
+        // World x, chunk x, local x
+        let cases = [(-1, -1, 15), (-16, -1, 0), (-17, -2, 15), (0, 0, 0), (15, 0, 15), (16, 1, 0)];
+        for (world, chunk, local) in cases {
+            let pos = WorldPos::new(world, 7, world);
+            assert_eq!(pos.chunk(), ChunkPos::new(chunk, chunk), "chunk of {}", world);
+            assert_eq!(pos.local(), LocalPos { x: local, y: 7, z: local }, "local of {}", world);
+        }
+    }
+
+    #[test]
+    fn round_trip_through_origin() {
+        for x in -40..40 {
+            for z in [-33, -17, -16, -1, 0, 1, 31] {
+                let pos = WorldPos::new(x, 100, z);
+                assert_eq!(pos.chunk().origin() + pos.local(), pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// Deterministic hashing of world positions, so anything derived from the seed and a
+// position comes out the same no matter in which order chunks are generated
+
+// Mixes the bits of a 64 bit value (the splitmix64 finalizer)
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
+pub fn hash3(seed: u32, x: i64, y: i64, z: i64) -> u64 {
+    let mut hash = mix((seed as u64) ^ 0x9e3779b97f4a7c15);
+    hash = mix(hash ^ (x as u64));
+    hash = mix(hash ^ (y as u64));
+    mix(hash ^ (z as u64))
+}
+
+// Maps a hash onto [0, 1)
+pub fn unit_f64(hash: u64) -> f64 {
+    ((hash >> 11) as f64) / ((1u64 << 53) as f64)
+}
+
+// A small seeded random number generator (splitmix64), for generation steps that need a
+// stream of numbers rather than one value per position. Seed it from hash3 of a chunk or
+// region so the stream doesn't depend on generation order.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.state)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        unit_f64(self.next_u64())
+    }
+
+    // Uniform in [min, max)
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next_f64() * (max - min)
+    }
+
+    // Uniform integer in [min, max]
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        let span = (max as i64) - (min as i64) + 1;
+        ((min as i64) + ((self.next_u64() % (span.max(1) as u64)) as i64)) as i32
+    }
+
+    // Turns an average number of things into a whole number, rounding up at random with
+    // the chance of the fraction, e.g. 1.25 gives 2 a quarter of the time and 1 otherwise
+    pub fn count(&mut self, average: f64) -> u32 {
+        let whole = average.max(0.0).floor();
+        let extra = self.next_f64() < average - whole;
+        (whole as u32) + (extra as u32)
+    }
+}
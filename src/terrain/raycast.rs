@@ -0,0 +1,220 @@
+use cgmath::{ InnerSpace, Point3, Vector3 };
+
+use super::block::{ BlockId, BlockRegistry };
+use super::position::WorldPos;
+use super::world::World;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub block: BlockId,
+    pub pos: WorldPos,
+    // Points out of the face the ray entered the block through
+    pub normal: Vector3<i32>,
+    // Distance along the ray to where it enters the block
+    pub distance: f32,
+    // The cell in front of the hit face, where a block placed against it goes
+    pub adjacent: WorldPos,
+}
+
+// Walks the ray through the grid one cell at a time, visiting every cell it passes
+// through in order (Amanatides and Woo, "A Fast Voxel Traversal Algorithm"). Only solid
+// blocks stop the ray, and the cell it starts in is skipped so a camera inside a block
+// still picks the block in front of it. Unloaded chunks count as air.
+pub fn raycast(
+    world: &World,
+    registry: &BlockRegistry,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32
+) -> Option<RaycastHit> {
+    if direction.magnitude2() < 1.0e-12 {
+        return None;
+    }
+    let direction = direction.normalize();
+    let origin = [origin.x, origin.y, origin.z];
+    let direction = [direction.x, direction.y, direction.z];
+
+    let start = WorldPos::containing(Point3::from(origin));
+    let mut cell = [start.x, start.y, start.z];
+    let mut step = [0; 3];
+    // Distance along the ray to the next cell boundary on each axis
+    let mut next_boundary = [f32::INFINITY; 3];
+    // Distance along the ray between two boundaries on each axis
+    let mut boundary_spacing = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            next_boundary[axis] = ((cell[axis] + 1) as f32 - origin[axis]) / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            next_boundary[axis] = (cell[axis] as f32 - origin[axis]) / direction[axis];
+        } else {
+            continue;
+        }
+        boundary_spacing[axis] = 1.0 / direction[axis].abs();
+    }
+
+    loop {
+        let axis = if next_boundary[0] < next_boundary[1] {
+            if next_boundary[0] < next_boundary[2] { 0 } else { 2 }
+        } else if next_boundary[1] < next_boundary[2] {
+            1
+        } else {
+            2
+        };
+        let distance = next_boundary[axis];
+        if distance > max_distance {
+            return None;
+        }
+
+        let previous = cell;
+        cell[axis] += step[axis];
+        next_boundary[axis] += boundary_spacing[axis];
+
+        let pos = WorldPos::new(cell[0], cell[1], cell[2]);
+        let block = world.get_block(pos);
+        if registry.get(block).solid {
+            let mut normal = Vector3::new(0, 0, 0);
+            normal[axis] = -step[axis];
+            return Some(RaycastHit {
+                block,
+                pos,
+                normal,
+                distance,
+                adjacent: WorldPos::new(previous[0], previous[1], previous[2]),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::chunk::Chunk;
+    use crate::terrain::position::ChunkPos;
+
+    // A world with the chunks from -2 to 1 on both axes loaded and empty
+    fn empty_world() -> World {
+        let mut world = World::new();
+        for x in -2..=1 {
+            for z in -2..=1 {
+                world.insert_chunk(Chunk::new(ChunkPos::new(x, z)));
+            }
+        }
+        world
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1.0e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn crosses_chunk_border() {
+        let registry = BlockRegistry::default();
+        let mut world = empty_world();
+        world.set_block(WorldPos::new(20, 5, 3), BlockId::STONE);
+
+        let hit = raycast(
+            &world,
+            &registry,
+            Point3::new(10.5, 5.5, 3.5),
+            Vector3::new(1.0, 0.0, 0.0),
+            16.0
+        ).unwrap();
+        assert_eq!(hit.block, BlockId::STONE);
+        assert_eq!(hit.pos, WorldPos::new(20, 5, 3));
+        assert_eq!(hit.normal, Vector3::new(-1, 0, 0));
+        assert_close(hit.distance, 9.5);
+        assert_eq!(hit.adjacent, WorldPos::new(19, 5, 3));
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        let registry = BlockRegistry::default();
+        let mut world = empty_world();
+        world.set_block(WorldPos::new(-5, 5, -4), BlockId::DIRT);
+
+        // From chunk (0, -1) into chunk (-1, -1)
+        let hit = raycast(
+            &world,
+            &registry,
+            Point3::new(3.5, 5.5, -3.5),
+            Vector3::new(-1.0, 0.0, 0.0),
+            16.0
+        ).unwrap();
+        assert_eq!(hit.pos, WorldPos::new(-5, 5, -4));
+        assert_eq!(hit.normal, Vector3::new(1, 0, 0));
+        assert_close(hit.distance, 7.5);
+        assert_eq!(hit.adjacent, WorldPos::new(-4, 5, -4));
+    }
+
+    #[test]
+    fn diagonal_across_chunk_borders() {
+        let registry = BlockRegistry::default();
+        let mut world = empty_world();
+        // A wall facing +z, spanning chunks on both sides of x = 0
+        for x in -16..16 {
+            for y in 0..16 {
+                world.set_block(WorldPos::new(x, y, -20), BlockId::STONE);
+            }
+        }
+
+        let direction = Vector3::new(-0.3, 0.0, -1.0);
+        let hit = raycast(&world, &registry, Point3::new(1.2, 5.5, 0.7), direction, 32.0).unwrap();
+        // The ray reaches the wall's face at z = -19 after moving 19.7 along z
+        let distance = 19.7 * direction.magnitude();
+        assert_eq!(hit.pos, WorldPos::new(-5, 5, -20));
+        assert_eq!(hit.normal, Vector3::new(0, 0, 1));
+        assert_close(hit.distance, distance);
+        assert_eq!(hit.adjacent, WorldPos::new(-5, 5, -19));
+    }
+
+    #[test]
+    fn straight_down_into_negative_chunk() {
+        let registry = BlockRegistry::default();
+        let mut world = empty_world();
+        world.set_block(WorldPos::new(-1, 2, -17), BlockId::GRASS);
+
+        let hit = raycast(
+            &world,
+            &registry,
+            Point3::new(-0.5, 10.25, -16.5),
+            Vector3::new(0.0, -1.0, 0.0),
+            16.0
+        ).unwrap();
+        assert_eq!(hit.pos, WorldPos::new(-1, 2, -17));
+        assert_eq!(hit.normal, Vector3::new(0, 1, 0));
+        assert_close(hit.distance, 7.25);
+        assert_eq!(hit.adjacent, WorldPos::new(-1, 3, -17));
+    }
+
+    #[test]
+    fn passes_through_water() {
+        let registry = BlockRegistry::default();
+        let mut world = empty_world();
+        world.set_block(WorldPos::new(-3, 5, 0), BlockId::WATER);
+        world.set_block(WorldPos::new(-6, 5, 0), BlockId::SAND);
+
+        let hit = raycast(
+            &world,
+            &registry,
+            Point3::new(0.5, 5.5, 0.5),
+            Vector3::new(-1.0, 0.0, 0.0),
+            16.0
+        ).unwrap();
+        assert_eq!(hit.block, BlockId::SAND);
+        assert_eq!(hit.pos, WorldPos::new(-6, 5, 0));
+    }
+
+    #[test]
+    fn out_of_reach() {
+        let registry = BlockRegistry::default();
+        let mut world = empty_world();
+        world.set_block(WorldPos::new(-10, 5, 0), BlockId::STONE);
+
+        let origin = Point3::new(0.5, 5.5, 0.5);
+        let direction = Vector3::new(-1.0, 0.0, 0.0);
+        assert!(raycast(&world, &registry, origin, direction, 8.0).is_none());
+        assert!(raycast(&world, &registry, origin, direction, 10.0).is_some());
+    }
+}
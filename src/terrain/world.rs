@@ -0,0 +1,101 @@
+use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
+
+use super::block::BlockId;
+use super::chunk::{ Chunk, CHUNK_SIZE };
+use super::position::{ ChunkPos, WorldPos };
+
+// The loaded chunks of the world. Chunks are shared with the meshing workers, so they are
+// kept behind an Arc and copied on write if a worker still holds on to the old one.
+pub struct World {
+    chunks: HashMap<ChunkPos, Arc<Chunk>>,
+    // Chunks edited since the changes were last taken. A chunk also changes when a block
+    // right across its border does, since that can hide or reveal its faces.
+    changes: HashSet<ChunkPos>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            changes: HashSet::new(),
+        }
+    }
+
+    // Returns air for positions in chunks that aren't loaded
+    pub fn get_block(&self, pos: WorldPos) -> BlockId {
+        match self.chunks.get(&pos.chunk()) {
+            Some(chunk) => {
+                let local = pos.local();
+                chunk.get(local.x, local.y, local.z)
+            }
+            None => BlockId::AIR,
+        }
+    }
+
+    // Returns false if the block's chunk isn't loaded or the position is above or below
+    // the world, in which case nothing changes
+    pub fn set_block(&mut self, pos: WorldPos, block: BlockId) -> bool {
+        let local = pos.local();
+        if !Chunk::in_bounds(local.x, local.y, local.z) {
+            return false;
+        }
+        let Some(chunk) = self.chunks.get_mut(&pos.chunk()) else {
+            return false;
+        };
+        if chunk.get(local.x, local.y, local.z) == block {
+            return true;
+        }
+        Arc::make_mut(chunk).set(local.x, local.y, local.z, block);
+
+        let chunk_pos = pos.chunk();
+        self.mark_changed(chunk_pos);
+        if local.x == 0 {
+            self.mark_changed(chunk_pos.offset(-1, 0));
+        } else if local.x == CHUNK_SIZE - 1 {
+            self.mark_changed(chunk_pos.offset(1, 0));
+        }
+        if local.z == 0 {
+            self.mark_changed(chunk_pos.offset(0, -1));
+        } else if local.z == CHUNK_SIZE - 1 {
+            self.mark_changed(chunk_pos.offset(0, 1));
+        }
+        true
+    }
+
+    fn mark_changed(&mut self, pos: ChunkPos) {
+        if self.chunks.contains_key(&pos) {
+            self.changes.insert(pos);
+        }
+    }
+
+    // Hands out the chunks edited since the last call and forgets about them
+    pub fn take_changes(&mut self) -> Vec<ChunkPos> {
+        self.changes.drain().collect()
+    }
+
+    pub fn chunk(&self, pos: ChunkPos) -> Option<&Arc<Chunk>> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn contains_chunk(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk) {
+        self.chunks.insert(chunk.pos, Arc::new(chunk));
+    }
+
+    pub fn remove_chunk(&mut self, pos: ChunkPos) {
+        self.chunks.remove(&pos);
+        self.changes.remove(&pos);
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Arc<Chunk>> {
+        self.chunks.values()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::{ mpsc, Arc, Mutex };
+use std::thread;
+
+// Number of workers shared by all pools, leaving a core free for the main thread
+fn available_threads() -> usize {
+    thread::available_parallelism()
+        .map(|count| count.get().saturating_sub(1))
+        .unwrap_or(1)
+        .max(1)
+}
+
+// Workers for the chunk generation pool. Generating takes longer than meshing, so it gets
+// the larger half of the available threads.
+pub fn generation_threads() -> usize {
+    available_threads().div_ceil(2)
+}
+
+// Workers for the chunk meshing pool, the rest of the available threads. Every pool needs
+// at least one worker, so with a single thread available the two pools share it.
+pub fn meshing_threads() -> usize {
+    (available_threads() - generation_threads()).max(1)
+}
+
+// Returned when submitting a job. Cancelling stops the job from running if no worker has
+// picked it up yet. A job that was already running still sends its result, so callers
+// should use the id to ignore results they are no longer waiting for.
+pub struct JobHandle {
+    pub id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Job<J> {
+    id: u64,
+    input: J,
+    cancelled: Arc<AtomicBool>,
+}
+
+// A fixed set of threads running the same work function on submitted jobs. Results are
+// collected through a channel and picked up with results() without blocking.
+pub struct WorkerPool<J, R> {
+    sender: Option<mpsc::Sender<Job<J>>>,
+    results: mpsc::Receiver<(u64, R)>,
+    workers: Vec<thread::JoinHandle<()>>,
+    next_id: u64,
+}
+
+impl<J: Send + 'static, R: Send + 'static> WorkerPool<J, R> {
+    pub fn new<F>(name: &str, threads: usize, work: F) -> Self where F: Fn(J) -> R + Send + Sync + 'static {
+        let (sender, receiver) = mpsc::channel::<Job<J>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let (result_sender, results) = mpsc::channel();
+        let work = Arc::new(work);
+
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                let receiver = Arc::clone(&receiver);
+                let result_sender = result_sender.clone();
+                let work = Arc::clone(&work);
+
+                thread::Builder
+                    ::new()
+                    .name(format!("{}-{}", name, index))
+                    .spawn(move || {
+                        loop {
+                            // The lock is only held while waiting for the next job
+                            let job = match receiver.lock().unwrap().recv() {
+                                Ok(job) => job,
+                                Err(_) => break, // The pool was dropped
+                            };
+
+                            if job.cancelled.load(Ordering::Relaxed) {
+                                continue;
+                            }
+
+                            let result = work(job.input);
+                            if result_sender.send((job.id, result)).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            results,
+            workers,
+            next_id: 0,
+        }
+    }
+
+    pub fn submit(&mut self, input: J) -> JobHandle {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            id,
+            input,
+            cancelled: Arc::clone(&cancelled),
+        };
+        if let Some(sender) = &self.sender {
+            sender.send(job).expect("Worker threads have stopped");
+        }
+
+        JobHandle { id, cancelled }
+    }
+
+    // Results of all jobs that finished since the last call, paired with their job ids
+    pub fn results(&self) -> impl Iterator<Item = (u64, R)> + '_ {
+        self.results.try_iter()
+    }
+}
+
+impl<J, R> Drop for WorkerPool<J, R> {
+    fn drop(&mut self) {
+        // Closing the channel makes every worker exit once it's done with its current job
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}